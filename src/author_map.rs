@@ -0,0 +1,74 @@
+//! Mapping the many identities one person commits under to a single name, for `--author-map`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Raw commit identities, names or emails, mapped to their canonical author name.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorMap {
+    /// Canonical names keyed by alias; email aliases are lowercased.
+    aliases: HashMap<String, String>,
+}
+
+impl AuthorMap {
+    /// Reads an author map file, parsed as JSON if it ends in `.json` and as TOML otherwise.
+    ///
+    /// Each key is a canonical name and its value lists the names and emails that
+    /// stand for it, e.g. `Swifty = ["swiftyos", "swifty@work.example.com"]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the author map file.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the AuthorMap, or an error naming the file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let entries: BTreeMap<String, Vec<String>> = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?,
+            _ => toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?,
+        };
+        Ok(AuthorMap::new(entries))
+    }
+
+    /// Creates a map from canonical names to the aliases that stand for them.
+    pub fn new(entries: BTreeMap<String, Vec<String>>) -> Self {
+        let mut aliases = HashMap::new();
+        for (canonical, identities) in entries {
+            for identity in identities {
+                aliases.insert(alias_key(&identity), canonical.clone());
+            }
+        }
+        AuthorMap { aliases }
+    }
+
+    /// Returns the canonical name of a configured identity, or the identity itself
+    /// if it is not an alias.
+    pub fn canonical<'a>(&'a self, identity: &'a str) -> &'a str {
+        self.aliases.get(&alias_key(identity)).map(String::as_str).unwrap_or(identity)
+    }
+
+    /// Returns the canonical name a commit signature stands for, looking up its
+    /// name before its email.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the signature.
+    /// * `email` - The email of the signature.
+    pub fn resolve(&self, name: &str, email: &str) -> Option<&str> {
+        [name, email]
+            .into_iter()
+            .filter(|identity| !identity.is_empty())
+            .find_map(|identity| self.aliases.get(&alias_key(identity)))
+            .map(String::as_str)
+    }
+}
+
+/// Emails are matched case-insensitively, names exactly.
+fn alias_key(identity: &str) -> String {
+    match identity.contains('@') {
+        true => identity.to_ascii_lowercase(),
+        false => identity.to_string(),
+    }
+}
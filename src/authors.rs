@@ -0,0 +1,76 @@
+//! Listing the authors seen in a repository's history, to pick `--author` from.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use git2::Repository;
+
+use crate::{open_repository, walk_history, AuthorsOpt, CollectOptions};
+
+/// An author identity with the number of commits made under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorCount {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+}
+
+/// Runs the `authors` subcommand, printing who committed in the last days.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed authors options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: AuthorsOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let (repo, repo_name) = open_repository(&opt.path)?;
+    let authors = count_authors(&repo, opt.days)?;
+    if authors.is_empty() {
+        println!("{}: no commits in the last {} day(s)", repo_name, opt.days);
+        return Ok(());
+    }
+
+    let width = authors.iter().map(|author| author.commits.to_string().len()).max().unwrap_or(0);
+    for author in &authors {
+        println!("{:>width$}  {} <{}>", author.commits, author.name, author.email, width = width);
+    }
+    Ok(())
+}
+
+/// Counts the commits of each name and email pair on the checked out branch.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `days` - How many days back to look, counted from now.
+///
+/// # Returns
+///
+/// A Result containing the authors, most commits first and then by name.
+pub fn count_authors(repo: &Repository, days: u32) -> Result<Vec<AuthorCount>, git2::Error> {
+    let since = (Utc::now() - Duration::days(days.into())).timestamp();
+    let mut counts: HashMap<(String, String), usize> = HashMap::new();
+
+    if let Some(revwalk) = walk_history(repo, &CollectOptions::default())? {
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            // Commit times are not ordered across merged branches, so the whole history is walked
+            if commit.time().seconds() < since {
+                continue;
+            }
+            let author = commit.author();
+            let identity = (
+                String::from_utf8_lossy(author.name_bytes()).into_owned(),
+                String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            );
+            *counts.entry(identity).or_default() += 1;
+        }
+    }
+
+    let mut authors: Vec<AuthorCount> =
+        counts.into_iter().map(|((name, email), commits)| AuthorCount { name, email, commits }).collect();
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| (&a.name, &a.email).cmp(&(&b.name, &b.email))));
+    Ok(authors)
+}
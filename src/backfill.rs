@@ -0,0 +1,250 @@
+//! One-shot import of the committed changes of past days.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use chrono::{Duration, Local, NaiveDate};
+use git2::Repository;
+use tracing::{info, warn};
+
+use crate::author_map::AuthorMap;
+use crate::report::start_of_day;
+use crate::store::{self, Store};
+use crate::{
+    changes_from_stats, commit_date, count_commit, current_branch, day_of, new_session_id, open_repository,
+    repository_names, validate_repositories, walk_history, AuthorFilter, BackfillOpt, ChangeFilter, CollectOptions,
+    LocChange, RepoStats,
+};
+
+/// Runs the `backfill` subcommand, importing the requested days of every repository.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed backfill options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: BackfillOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let aliases = match &opt.author_map {
+        Some(path) => AuthorMap::load(path)?,
+        None => AuthorMap::default(),
+    };
+    let authors = AuthorFilter::new(opt.authors, opt.author_emails, aliases);
+    let options = CollectOptions {
+        count_merges: opt.count_merges,
+        by_hunk: opt.by_hunk,
+        exclude: opt.exclude,
+        skip_message: opt.skip_message_regex,
+        timezone: opt.timezone,
+        day_start_hour: opt.day_start_hour,
+        count_binary: opt.count_binary,
+        branch: opt.branch,
+        ignore_whitespace: opt.ignore_whitespace,
+        significant_lines_only: opt.significant_lines_only,
+        flag_large_commit: opt.flag_large_commit,
+        // The history is imported from its start, so the first commit's contents count too
+        count_root_commits: true,
+        ..Default::default()
+    };
+    let mut paths = opt.paths;
+    paths.extend(opt.named_repos.iter().map(|(_, path)| path.clone()));
+    let paths = validate_repositories(paths, false, !opt.no_canonicalize)?;
+    let repo_names = repository_names(&paths, &opt.named_repos);
+
+    // Today is left to the watcher, whose snapshots also include pending work
+    let until = day_of(&Local::now(), opt.day_start_hour);
+    let since = until - Duration::days(opt.days.into());
+
+    let session_id = opt.session_id.unwrap_or_else(new_session_id);
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+
+    for path in &paths {
+        let (repo, repo_name) = open_repository(path)?;
+        let repo_name = repo_names.get(path).cloned().unwrap_or(repo_name);
+
+        let history = collect_history(&repo, &authors, &options, since, until)?;
+        let branch = current_branch(&repo);
+        let imported =
+            store_history(store.as_ref(), &repo_name, branch.as_deref(), &history, &session_id, opt.day_start_hour)
+                .await?;
+        let commits: usize = history.values().map(|stats| stats.commits.len()).sum();
+        println!("{}: imported {} of {} commit(s)", repo_name, imported, commits);
+    }
+
+    store.close().await;
+    Ok(())
+}
+
+/// Stores the commits made since the newest stored row, for `watch --catch-up`.
+///
+/// Every day from the one of the newest row through today is walked, so the
+/// commits made while the watcher was not running get rows of their own with
+/// their commit times. Commits already stored are skipped.
+///
+/// # Arguments
+///
+/// * `store` - The Store the rows are written to.
+/// * `paths` - The paths to the tracked repositories.
+/// * `repo_names` - The name each repository is stored under, by path.
+/// * `authors` - A reference to the AuthorFilter describing whom to count.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// * `session_id` - The id of this run, stored on every row.
+///
+/// # Returns
+///
+/// A Result containing the number of commits stored. A repository that cannot
+/// be read is only warned about.
+pub async fn catch_up(
+    store: &dyn Store,
+    paths: &[PathBuf],
+    repo_names: &HashMap<PathBuf, String>,
+    authors: &AuthorFilter,
+    options: &CollectOptions,
+    session_id: &str,
+) -> Result<usize, sqlx::Error> {
+    let last = match store.last_timestamp().await? {
+        Some(last) => last,
+        // With nothing stored, the first poll stores today's commits one by one anyway
+        None => return Ok(0),
+    };
+    let since = day_of(&last.with_timezone(&Local), options.day_start_hour);
+    let until = day_of(&Local::now(), options.day_start_hour) + Duration::days(1);
+
+    let mut imported = 0;
+    for path in paths {
+        let repo_name = match repo_names.get(path) {
+            Some(repo_name) => repo_name,
+            None => continue,
+        };
+        let history = Repository::open(path)
+            .and_then(|repo| Ok((collect_history(&repo, authors, options, since, until)?, current_branch(&repo))));
+        match history {
+            Ok((history, branch)) => {
+                let stored =
+                    store_history(store, repo_name, branch.as_deref(), &history, session_id, options.day_start_hour)
+                        .await?;
+                info!(repo = %repo_name, %since, commits = stored, "caught up on the commits made while not watching");
+                imported += stored;
+            }
+            Err(e) => warn!(repo = %repo_name, reason = e.message(), "cannot catch up, leaving it to the first poll"),
+        }
+    }
+    Ok(imported)
+}
+
+/// Sums the commits of each author per local day over a range of days.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `authors` - A reference to the AuthorFilter describing whom to count.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// * `since` - The first day to include.
+/// * `until` - The day to stop at, which is not included.
+///
+/// # Returns
+///
+/// A Result containing the stats per day and author, with the commits they sum.
+pub fn collect_history(
+    repo: &Repository,
+    authors: &AuthorFilter,
+    options: &CollectOptions,
+    since: NaiveDate,
+    until: NaiveDate,
+) -> Result<BTreeMap<(NaiveDate, String), RepoStats>, git2::Error> {
+    let mut history: BTreeMap<(NaiveDate, String), RepoStats> = BTreeMap::new();
+
+    let revwalk = match walk_history(repo, options)? {
+        Some(revwalk) => revwalk,
+        None => return Ok(history),
+    };
+
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+
+        // Commit times are not ordered across merged branches, so the whole history is walked
+        let date = match commit_date(&commit.time(), options.timezone, options.day_start_hour) {
+            Some(date) if date >= since && date < until => date,
+            _ => continue,
+        };
+
+        if options.skips_commit(&commit) {
+            continue;
+        }
+
+        let author = match authors.matching(&commit.author()) {
+            Some(author) => author,
+            None => continue,
+        };
+
+        if let Some(counts) = count_commit(repo, &commit, options)? {
+            let flagged = options.flags_commit(&counts);
+            history.entry((date, author.clone())).or_default().add_commit(&commit, counts, flagged);
+        }
+    }
+
+    Ok(history)
+}
+
+/// Stores a row for each commit that is not stored yet.
+///
+/// Days that already have a snapshot of the committed totals, written by the
+/// watcher before it stored commits one by one, are skipped as they cover the
+/// day's commits already.
+///
+/// # Arguments
+///
+/// * `store` - The Store the rows are written to.
+/// * `repo_name` - The name of the repository the history belongs to.
+/// * `branch` - The branch whose history was walked.
+/// * `history` - The stats per day and author from `collect_history`.
+/// * `session_id` - The id of this run, stored on every row.
+/// * `day_start_hour` - The hour the days of `history` start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A Result containing the number of commits stored.
+pub async fn store_history(
+    store: &dyn Store,
+    repo_name: &str,
+    branch: Option<&str>,
+    history: &BTreeMap<(NaiveDate, String), RepoStats>,
+    session_id: &str,
+    day_start_hour: u32,
+) -> Result<usize, sqlx::Error> {
+    let mut imported = 0;
+
+    for ((date, author), stats) in history {
+        let filter = ChangeFilter {
+            author: Some(author.clone()),
+            repo_name: Some(repo_name.to_string()),
+            since: Some(start_of_day(*date, day_start_hour)),
+            until: Some(start_of_day(*date + Duration::days(1), day_start_hour)),
+        };
+        let existing = store.query_range(&filter).await?;
+        if existing.iter().any(|change| change.is_committed && change.commit_sha.is_none()) {
+            continue;
+        }
+        let stored: HashSet<&str> = existing.iter().filter_map(|change| change.commit_sha.as_deref()).collect();
+
+        let start = start_of_day(*date, day_start_hour);
+        for change in changes_from_stats(repo_name, author, branch, stats, start)
+            .iter()
+            .filter(|change| change.is_committed)
+        {
+            let sha = change.commit_sha.as_deref().unwrap_or_default();
+            if stored.contains(sha) {
+                continue;
+            }
+            let change = LocChange { session_id: Some(session_id.to_string()), ..change.clone() };
+            store.store_change(&change).await?;
+            if change.is_total() {
+                imported += 1;
+            }
+        }
+    }
+
+    Ok(imported)
+}
@@ -0,0 +1,180 @@
+//! Comparing the committed LoC of the current period with the previous one, for `compare`.
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Local, NaiveDate};
+
+use crate::report::{align_table, daily_totals_from, start_of_day};
+use crate::status::{format_signed, DeletionsAs};
+use crate::{day_of, store, ChangeFilter, CompareOpt, LocChange};
+
+/// The committed LoC of a repository in the previous and the current period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeriodTotals {
+    pub previous: i32,
+    pub current: i32,
+}
+
+impl PeriodTotals {
+    /// How much the committed LoC changed from the previous period.
+    pub fn delta(&self) -> i32 {
+        self.current - self.previous
+    }
+
+    /// The change as a percentage of the previous period, e.g. `+25.0%`.
+    ///
+    /// # Returns
+    ///
+    /// The percentage, `new` when nothing was committed in the previous period,
+    /// or `-` when nothing was committed in either.
+    pub fn percent_change(&self) -> String {
+        match (self.previous, self.current) {
+            (0, 0) => "-".to_string(),
+            (0, _) => "new".to_string(),
+            (previous, _) => format!("{:+.1}%", f64::from(self.delta()) * 100.0 / f64::from(previous)),
+        }
+    }
+}
+
+/// Runs the `compare` subcommand, printing the committed LoC of the current
+/// period next to the previous one.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed compare options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: CompareOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let day_start_hour = opt.day_start_hour;
+    let current = opt.period.start_of(day_of(&Local::now(), day_start_hour));
+    let previous = opt.period.start_of(current - Duration::days(1));
+    let filter = ChangeFilter {
+        author: opt.author,
+        since: Some(start_of_day(previous, day_start_hour)),
+        ..Default::default()
+    };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let changes = store.query_range(&filter).await?;
+    store.close().await;
+
+    let totals = compare_periods(&changes, previous, current, day_start_hour);
+    print!("{}", format_comparison(&totals, &opt.period.period_of(previous), &opt.period.period_of(current)));
+    Ok(())
+}
+
+/// Sums the committed LoC of each repository in the previous and the current period.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to sum.
+/// * `previous` - The first local day of the previous period.
+/// * `current` - The first local day of the current period, which runs through today.
+/// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A map from repository name to its totals, for the repositories with commits in either period.
+pub fn compare_periods(
+    changes: &[LocChange],
+    previous: NaiveDate,
+    current: NaiveDate,
+    day_start_hour: u32,
+) -> BTreeMap<String, PeriodTotals> {
+    let (previous, current) = (start_of_day(previous, day_start_hour), start_of_day(current, day_start_hour));
+    let mut totals: BTreeMap<String, PeriodTotals> = BTreeMap::new();
+    for change in daily_totals_from(changes, day_start_hour).into_iter().filter(|change| change.is_committed) {
+        if change.timestamp < previous {
+            continue;
+        }
+        let loc = DeletionsAs::default().loc(change.additions, change.deletions);
+        let repo = totals.entry(change.repo_name).or_default();
+        match change.timestamp < current {
+            true => repo.previous += loc,
+            false => repo.current += loc,
+        }
+    }
+    totals
+}
+
+/// Formats the totals of both periods as an aligned table with a grand total row.
+///
+/// # Arguments
+///
+/// * `totals` - The totals per repository, from `compare_periods`.
+/// * `previous` - The name of the previous period, e.g. `2024-W10`.
+/// * `current` - The name of the current period.
+pub fn format_comparison(totals: &BTreeMap<String, PeriodTotals>, previous: &str, current: &str) -> String {
+    let mut grand_total = PeriodTotals::default();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (repo_name, repo) in totals {
+        grand_total.previous += repo.previous;
+        grand_total.current += repo.current;
+        rows.push(comparison_row(repo_name, repo));
+    }
+    rows.push(comparison_row("Total", &grand_total));
+
+    align_table(&["Repository"], &[previous, current, "Delta", "Change"], &rows)
+}
+
+fn comparison_row(label: &str, totals: &PeriodTotals) -> Vec<String> {
+    vec![
+        label.to_string(),
+        totals.previous.to_string(),
+        totals.current.to_string(),
+        format_signed(totals.delta()),
+        totals.percent_change(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{start_of_local_day, GroupBy};
+    use crate::{changes_from_stats, RepoStats};
+
+    #[test]
+    fn this_week_is_compared_with_last_week_per_repository() {
+        // A Wednesday, so the current week started two days before
+        let today = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap();
+        let current = GroupBy::Week.start_of(today);
+        let previous = GroupBy::Week.start_of(current - Duration::days(1));
+        assert_eq!((current, previous), (today - Duration::days(2), today - Duration::days(9)));
+
+        let at = |day: NaiveDate| start_of_local_day(day) + Duration::hours(12);
+        let rows = [
+            // The Sunday before the previous week
+            ("api", at(previous - Duration::days(1)), "a0", 500, 0),
+            ("api", at(previous), "a1", 60, 20),
+            ("api", at(previous + Duration::days(6)), "a2", 20, 0),
+            ("api", at(current), "a3", 90, 10),
+            ("api", at(today), "a4", 20, 0),
+            ("web", at(today), "w1", 30, 5),
+        ];
+        let changes: Vec<LocChange> = rows
+            .iter()
+            .flat_map(|(repo, at, sha, additions, deletions)| {
+                changes_from_stats(repo, "Me", None, &RepoStats::with_commit(sha, *at, *additions, *deletions), *at)
+            })
+            .collect();
+
+        let totals = compare_periods(&changes, previous, current, 0);
+        assert_eq!(totals["api"], PeriodTotals { previous: 100, current: 120 });
+        assert_eq!(totals["api"].percent_change(), "+20.0%");
+        // Nothing was committed to it the week before
+        assert_eq!(totals["web"], PeriodTotals { previous: 0, current: 35 });
+        assert_eq!(totals["web"].percent_change(), "new");
+        assert_eq!(PeriodTotals { previous: 40, current: 10 }.percent_change(), "-75.0%");
+        assert_eq!(PeriodTotals::default().percent_change(), "-");
+
+        let table = format_comparison(&totals, "2024-W10", "2024-W11");
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("Repository") && lines[0].contains("2024-W10") && lines[0].contains("Change"));
+        assert!(lines[2].starts_with("web") && lines[2].ends_with("new"));
+        let total: Vec<&str> = lines.last().unwrap().split_whitespace().collect();
+        assert_eq!(total, ["Total", "100", "155", "+55", "+55.0%"]);
+    }
+}
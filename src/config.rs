@@ -0,0 +1,309 @@
+//! The optional TOML config file holding defaults for the `watch` flags.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::status::{RepoColor, RepoStyle};
+
+/// Values read from the config file; every field is optional.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Paths to the git repositories to track.
+    pub paths: Vec<PathBuf>,
+    /// The authors whose changes will be tracked.
+    pub authors: Vec<String>,
+    /// Path to the database used to store changes, or a `postgres://` URL.
+    pub db_path: Option<String>,
+    /// How long the watcher waits for file events to settle, in seconds.
+    pub interval_secs: Option<u64>,
+    /// Globs of paths to leave out of the counts.
+    pub exclude: Vec<String>,
+    /// The committed LoC aimed for each day.
+    pub daily_goal: Option<u32>,
+    /// The extensions of the files counted as docs by `--by-category`.
+    pub doc_extensions: Vec<String>,
+    /// How many days of stored rows to keep, see `--retain-days`.
+    pub retain_days: Option<u32>,
+    /// How some of the repositories are shown, one `[[repos]]` table each.
+    pub repos: Vec<RepoConfig>,
+}
+
+/// The label, color and tracking of a repository, set in a `[[repos]]` table.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    /// The path of the repository, as tracked.
+    pub path: PathBuf,
+    /// The label shown in place of the directory name.
+    pub label: Option<String>,
+    /// The name of the color the label is shown in, e.g. `cyan` or `bright-red`.
+    pub color: Option<String>,
+    /// Set to false to stop tracking the repository while keeping its table.
+    pub enabled: Option<bool>,
+}
+
+/// Resolves the `[[repos]]` tables to the style of each tracked repository.
+///
+/// A table matches a repository by its path, compared as given or canonicalized.
+/// Unknown colors and paths that are not tracked are warned about and left out,
+/// so a typo never stops the watcher.
+///
+/// # Arguments
+///
+/// * `repos` - The `[[repos]]` tables of the config file.
+/// * `repo_names` - The name each tracked repository is stored under, by path.
+///
+/// # Returns
+///
+/// The configured styles by repository name, falling back to the name and an
+/// automatic color for what a table leaves out.
+pub fn repo_styles(repos: &[RepoConfig], repo_names: &HashMap<PathBuf, String>) -> BTreeMap<String, RepoStyle> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut styles = BTreeMap::new();
+
+    for repo in repos {
+        let target = canonical(&repo.path);
+        let repo_name = match repo_names.iter().find(|(path, _)| **path == repo.path || canonical(path) == target) {
+            Some((_, repo_name)) => repo_name,
+            None => {
+                warn!(path = %repo.path.display(), "config sets a label or color for a repository that is not tracked");
+                continue;
+            }
+        };
+
+        let color = match repo.color.as_deref().map(str::parse::<RepoColor>) {
+            Some(Ok(color)) => color,
+            Some(Err(e)) => {
+                warn!(repo = %repo_name, error = %e, "using an automatic color instead");
+                RepoColor::auto(repo_name)
+            }
+            None => RepoColor::auto(repo_name),
+        };
+        let label = repo.label.clone().unwrap_or_else(|| repo_name.clone());
+        styles.insert(repo_name.clone(), RepoStyle { label, color });
+    }
+
+    styles
+}
+
+/// Finds the tracked repositories that are disabled, by `enabled = false` in
+/// their `[[repos]]` table or by `--disable`, unless `--enable` turns them back on.
+///
+/// `--disable` and `--enable` take a repository's name or path. Names that match
+/// no tracked repository are warned about.
+///
+/// # Arguments
+///
+/// * `repos` - The `[[repos]]` tables of the config file.
+/// * `repo_names` - The name each tracked repository is stored under, by path.
+/// * `disable` - The repositories given with `--disable`.
+/// * `enable` - The repositories given with `--enable`.
+///
+/// # Returns
+///
+/// A Result containing the paths of the disabled repositories, or an error if a
+/// repository is given to both `--disable` and `--enable`.
+pub fn disabled_repos(
+    repos: &[RepoConfig],
+    repo_names: &HashMap<PathBuf, String>,
+    disable: &[String],
+    enable: &[String],
+) -> Result<HashSet<PathBuf>, String> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let matching = |given: &String| -> Option<&PathBuf> {
+        let found = repo_names.iter().find(|(path, repo_name)| {
+            *repo_name == given || path.as_path() == Path::new(given) || canonical(path) == canonical(Path::new(given))
+        });
+        if found.is_none() {
+            warn!(repo = %given, "no tracked repository has this name or path");
+        }
+        found.map(|(path, _)| path)
+    };
+
+    let mut disabled: HashSet<PathBuf> = repos
+        .iter()
+        .filter(|repo| repo.enabled == Some(false))
+        .filter_map(|repo| {
+            let target = canonical(&repo.path);
+            repo_names.keys().find(|path| **path == repo.path || canonical(path) == target).cloned()
+        })
+        .collect();
+    let disable: HashSet<&PathBuf> = disable.iter().filter_map(matching).collect();
+    for path in enable.iter().filter_map(matching) {
+        if disable.contains(path) {
+            return Err(format!("{} is given to both --disable and --enable", repo_names[path]));
+        }
+        disabled.remove(path);
+    }
+    disabled.extend(disable.into_iter().cloned());
+    Ok(disabled)
+}
+
+/// Returns `~/.config/devmetrics/config.toml`, if the home directory is known.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".config/devmetrics/config.toml"))
+}
+
+/// Reads the config file.
+///
+/// # Arguments
+///
+/// * `path` - The config file given with `--config`, or None for the default location.
+///
+/// # Returns
+///
+/// A Result containing the parsed Config. A missing file at the default location
+/// yields an empty Config, while a missing file given explicitly is an error.
+pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn std::error::Error>> {
+    let (path, explicit) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(Config::default()),
+        Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+    };
+
+    toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WatchOpt;
+    use structopt::StructOpt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn config_values_fill_in_flags_not_given_on_the_command_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            paths = ["/src/api", "/src/web"]
+            authors = ["Swifty"]
+            db_path = "/var/lib/devmetrics/stats.db"
+            interval_secs = 30
+            exclude = ["target/**", "*.lock"]
+            daily_goal = 800
+            "#,
+        )
+        .unwrap();
+
+        let config_arg = path.to_str().unwrap();
+        let mut opt = WatchOpt::from_iter(["watch", "--config", config_arg, "--interval-secs", "5"]);
+        opt.apply_config(load(Some(&path)).unwrap()).unwrap();
+
+        assert_eq!(opt.paths, [PathBuf::from("/src/api"), PathBuf::from("/src/web")]);
+        assert_eq!(opt.authors, ["Swifty"]);
+        assert_eq!(opt.db_path(), "/var/lib/devmetrics/stats.db");
+        assert_eq!(opt.interval_secs(), 5);
+        assert_eq!(opt.exclude, ["target/**", "*.lock"]);
+        assert_eq!(opt.daily_goal, Some(800));
+
+        // Flags given on the command line win over the file
+        let mut opt = WatchOpt::from_iter(["watch", "/src/cli", "-a", "Other", "--config", config_arg]);
+        opt.apply_config(load(Some(&path)).unwrap()).unwrap();
+        assert_eq!(opt.paths, [PathBuf::from("/src/cli")]);
+        assert_eq!(opt.authors, ["Other"]);
+        assert_eq!(opt.interval_secs(), 30);
+    }
+
+    #[test]
+    fn repositories_get_the_label_and_color_set_for_their_path() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[repos]]
+            path = "/src/api"
+            label = "Backend"
+            color = "bright-cyan"
+
+            [[repos]]
+            path = "/src/web"
+            color = "mauve"
+
+            [[repos]]
+            path = "/src/gone"
+            label = "Gone"
+            "#,
+        )
+        .unwrap();
+        let config = load(Some(&path)).unwrap();
+
+        let repo_names: HashMap<PathBuf, String> = [
+            (PathBuf::from("/src/api"), "api".to_string()),
+            (PathBuf::from("/src/web"), "web".to_string()),
+            (PathBuf::from("/src/cli"), "cli".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let styles = repo_styles(&config.repos, &repo_names);
+
+        assert_eq!(styles["api"], RepoStyle { label: "Backend".to_string(), color: RepoColor::BrightCyan });
+        // An unknown color is only warned about
+        assert_eq!(styles["web"], RepoStyle { label: "web".to_string(), color: RepoColor::auto("web") });
+        assert_eq!(styles.len(), 2);
+    }
+
+    #[test]
+    fn repositories_are_disabled_in_the_config_or_on_the_command_line() {
+        let config: Config = toml::from_str(
+            r#"
+            [[repos]]
+            path = "/src/api"
+            enabled = false
+
+            [[repos]]
+            path = "/src/web"
+            enabled = false
+
+            [[repos]]
+            path = "/src/cli"
+            label = "CLI"
+            "#,
+        )
+        .unwrap();
+        let repo_names: HashMap<PathBuf, String> = ["api", "web", "cli", "docs"]
+            .into_iter()
+            .map(|name| (Path::new("/src").join(name), name.to_string()))
+            .collect();
+        let disabled = |disable: &[&str], enable: &[&str]| {
+            let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            let mut paths: Vec<PathBuf> =
+                disabled_repos(&config.repos, &repo_names, &strings(disable), &strings(enable))?.into_iter().collect();
+            paths.sort();
+            Ok::<_, String>(paths)
+        };
+
+        assert_eq!(disabled(&[], &[]).unwrap(), [PathBuf::from("/src/api"), PathBuf::from("/src/web")]);
+        // By name or by path, and a name that is not tracked is only warned about
+        assert_eq!(
+            disabled(&["docs"], &["/src/api", "gone"]).unwrap(),
+            [PathBuf::from("/src/docs"), PathBuf::from("/src/web")]
+        );
+        assert!(disabled(&["cli"], &["/src/cli"]).is_err());
+    }
+
+    #[test]
+    fn unknown_keys_and_missing_explicit_files_are_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "author = \"typo\"\n").unwrap();
+
+        assert!(load(Some(&path)).is_err());
+        assert!(load(Some(&dir.path().join("missing.toml"))).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! A Unix socket answering line commands with the stats of the latest poll.
+
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::debug;
+
+/// The status served on the socket, shared between the watch loop and the server.
+#[derive(Debug, Clone, Default)]
+pub struct Control {
+    /// The latest status as a JSON line, in the format of `--format json`.
+    status: Arc<RwLock<String>>,
+}
+
+impl Control {
+    /// Replaces the served status with the one of the latest poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status rendered as a JSON line.
+    pub fn update(&self, status: String) {
+        *self.status.write().expect("control lock poisoned") = status;
+    }
+
+    /// Answers a single command, the reply ending in a newline.
+    ///
+    /// `stats` replies with the latest status and `ping` with `pong`; anything
+    /// else gets a JSON object with an `error`.
+    pub fn reply(&self, command: &str) -> String {
+        match command {
+            "stats" => self.status.read().expect("control lock poisoned").clone(),
+            "ping" => "pong\n".to_string(),
+            _ => format!("{}\n", json!({ "error": format!("unknown command `{}`, try `stats`", command) })),
+        }
+    }
+}
+
+/// Binds the control socket, replacing a stale socket left by an earlier run.
+///
+/// # Arguments
+///
+/// * `path` - Where to create the socket.
+///
+/// # Returns
+///
+/// A Result containing the listener, or an error if the path is taken by something
+/// other than a socket.
+pub fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ))
+        }
+        Err(_) => {}
+    }
+    UnixListener::bind(path)
+}
+
+/// Answers the clients of the control socket until the process exits.
+///
+/// Each client is served on its own task, one reply per line it sends, until it disconnects.
+///
+/// # Arguments
+///
+/// * `listener` - The bound listener to accept clients on.
+/// * `control` - The Control updated by the watch loop.
+///
+/// # Returns
+///
+/// A Result that is only returned early if accepting clients fails.
+pub async fn serve(listener: UnixListener, control: Control) -> std::io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, &control).await {
+                debug!(error = %e, "control client disconnected");
+            }
+        });
+    }
+}
+
+async fn answer(stream: UnixStream, control: &Control) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        writer.write_all(control.reply(command).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn clients_get_the_latest_stats_on_request() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("control.sock");
+        // A stale socket from an earlier run is replaced, any other file is left alone
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = bind(&path).unwrap();
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, "keep").unwrap();
+        assert!(bind(&notes).is_err());
+        assert!(notes.exists());
+
+        let control = Control::default();
+        control.update("{\"repos\":[]}\n".to_string());
+        tokio::spawn(serve(listener, control.clone()));
+
+        // A client that leaves without sending anything does not stop the server
+        drop(UnixStream::connect(&path).await.unwrap());
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut replies = BufReader::new(reader).lines();
+        writer.write_all(b"stats\n\nping\nbogus\n").await.unwrap();
+        assert_eq!(replies.next_line().await.unwrap().unwrap(), "{\"repos\":[]}");
+        assert_eq!(replies.next_line().await.unwrap().unwrap(), "pong");
+        let error: serde_json::Value = serde_json::from_str(&replies.next_line().await.unwrap().unwrap()).unwrap();
+        assert!(error["error"].as_str().unwrap().contains("bogus"));
+
+        control.update("{\"repos\":[{\"repo\":\"api\"}]}\n".to_string());
+        writer.write_all(b"stats\n").await.unwrap();
+        assert_eq!(replies.next_line().await.unwrap().unwrap(), "{\"repos\":[{\"repo\":\"api\"}]}");
+    }
+}
@@ -0,0 +1,80 @@
+//! Finding the git repositories below a root directory, for `--discover`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Collects every directory below a root that holds a `.git` entry.
+///
+/// Symlinked directories are not followed, and subdirectories that cannot be
+/// read are skipped.
+///
+/// # Arguments
+///
+/// * `root` - The directory to search.
+/// * `max_depth` - How many levels below the root are searched; the root itself is level 0.
+/// * `include_nested` - Whether to keep searching inside the repositories found.
+///
+/// # Returns
+///
+/// A Result containing the sorted paths of the repositories, or an error if the
+/// root cannot be read.
+pub fn discover_repositories(root: &Path, max_depth: usize, include_nested: bool) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    // Reading the root first turns a mistyped root into an error rather than an empty list
+    fs::read_dir(root)?;
+    search(root, 0, max_depth, include_nested, &mut found);
+    found.sort();
+    Ok(found)
+}
+
+fn search(dir: &Path, depth: usize, max_depth: usize, include_nested: bool, found: &mut Vec<PathBuf>) {
+    // A `.git` file rather than a directory marks a worktree or a submodule
+    if dir.join(".git").exists() {
+        found.push(dir.to_path_buf());
+        if !include_nested {
+            return;
+        }
+    }
+    if depth >= max_depth {
+        return;
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false);
+        if is_dir && entry.file_name() != ".git" {
+            search(&entry.path(), depth + 1, max_depth, include_nested, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn repositories_below_the_root_are_found() {
+        let root = TempDir::new().unwrap();
+        let api = root.path().join("api");
+        let web = root.path().join("clients").join("web");
+        let vendored = api.join("vendor").join("lib");
+        for repo in [&api, &web, &vendored] {
+            Repository::init(repo).unwrap();
+        }
+        let notes = root.path().join("notes");
+        fs::create_dir(&notes).unwrap();
+        fs::write(notes.join("todo.md"), "not a repository\n").unwrap();
+
+        assert_eq!(discover_repositories(root.path(), 3, false).unwrap(), [api.clone(), web.clone()]);
+        assert_eq!(discover_repositories(root.path(), 3, true).unwrap(), [api.clone(), vendored, web]);
+        // `clients/web` is two levels down
+        assert_eq!(discover_repositories(root.path(), 1, false).unwrap(), [api]);
+        assert!(discover_repositories(&root.path().join("missing"), 3, false).is_err());
+    }
+}
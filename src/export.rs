@@ -0,0 +1,157 @@
+//! Export of the stored changes as CSV.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{store, ChangeFilter, ExportOpt, LocChange};
+
+/// Runs the `export` subcommand, writing the matching changes as CSV.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed export options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: ExportOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = ChangeFilter {
+        repo_name: opt.repo,
+        ..ChangeFilter::for_range(opt.since, opt.until)?
+    };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let changes = store.query_range(&filter).await?;
+    store.close().await;
+
+    match opt.output {
+        Some(path) => write_csv(&changes, File::create(path)?)?,
+        None => write_csv(&changes, io::stdout().lock())?,
+    }
+
+    Ok(())
+}
+
+/// A stored change as written to the CSV, with its derived net and churn.
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    repo_name: &'a str,
+    timestamp: DateTime<Utc>,
+    author: Option<&'a str>,
+    author_email: Option<&'a str>,
+    additions: i32,
+    deletions: i32,
+    is_committed: bool,
+    extension: Option<&'a str>,
+    branch: Option<&'a str>,
+    commit_sha: Option<&'a str>,
+    staged_additions: i32,
+    staged_deletions: i32,
+    files_changed: i32,
+    flagged: bool,
+    session_id: Option<&'a str>,
+    inserted_lines: i32,
+    edited_lines: i32,
+    category: Option<&'a str>,
+    is_merge: bool,
+    net: i32,
+    churn: i32,
+}
+
+impl<'a> From<&'a LocChange> for ExportRow<'a> {
+    fn from(change: &'a LocChange) -> Self {
+        ExportRow {
+            repo_name: &change.repo_name,
+            timestamp: change.timestamp,
+            author: change.author.as_deref(),
+            author_email: change.author_email.as_deref(),
+            additions: change.additions,
+            deletions: change.deletions,
+            is_committed: change.is_committed,
+            extension: change.extension.as_deref(),
+            branch: change.branch.as_deref(),
+            commit_sha: change.commit_sha.as_deref(),
+            staged_additions: change.staged_additions,
+            staged_deletions: change.staged_deletions,
+            files_changed: change.files_changed,
+            flagged: change.flagged,
+            session_id: change.session_id.as_deref(),
+            inserted_lines: change.inserted_lines,
+            edited_lines: change.edited_lines,
+            category: change.category.as_deref(),
+            is_merge: change.is_merge,
+            net: change.additions - change.deletions,
+            churn: change.additions + change.deletions,
+        }
+    }
+}
+
+/// Writes changes as CSV with a header row named after the table's columns,
+/// followed by the derived `net` and `churn` columns.
+///
+/// # Arguments
+///
+/// * `changes` - The changes to write.
+/// * `writer` - Where to write the CSV to.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub fn write_csv<W: Write>(changes: &[LocChange], writer: W) -> Result<(), csv::Error> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    for change in changes {
+        csv_writer.serialize(ExportRow::from(change))?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{SqliteStore, Store};
+    use crate::{changes_from_stats, RepoStats};
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn exported_rows_round_trip_through_csv() {
+        let store = SqliteStore::memory().await;
+
+        let timestamp = Utc::now();
+        let committed_at = timestamp - chrono::Duration::minutes(5);
+        let stats = RepoStats { pending_additions: 3, ..RepoStats::with_commit("a1b2c3", committed_at, 7, 2) };
+        for change in &changes_from_stats("api, v2", "Doe, Jane", Some("main"), &stats, timestamp) {
+            store.store_change(change).await.unwrap();
+        }
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let mut output = Vec::new();
+        write_csv(&changes, &mut output).unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with(
+            "repo_name,timestamp,author,author_email,additions,deletions,is_committed,extension,branch,commit_sha,staged_additions,staged_deletions,files_changed,flagged,session_id,inserted_lines,edited_lines,category,is_merge,net,churn\n"
+        ));
+        assert!(text.contains("\"api, v2\""));
+
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let parsed: Vec<LocChange> = reader.deserialize().map(Result::unwrap).collect();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].repo_name, "api, v2");
+        assert_eq!(parsed[0].author.as_deref(), Some("Doe, Jane"));
+        assert_eq!(parsed[0].timestamp, committed_at);
+        assert_eq!((parsed[0].additions, parsed[0].deletions), (7, 2));
+        assert!(parsed[0].is_committed);
+        assert_eq!(parsed[0].commit_sha.as_deref(), Some("a1b2c3"));
+        assert_eq!(parsed[1].timestamp, timestamp);
+        assert_eq!(parsed[1].additions, 3);
+        assert_eq!(parsed[1].extension, None);
+        assert_eq!(parsed[1].branch.as_deref(), Some("main"));
+        assert_eq!(parsed[1].commit_sha, None);
+        assert!(text.lines().nth(1).unwrap().ends_with(",main,a1b2c3,0,0,0,false,,0,0,,false,5,9"));
+    }
+}
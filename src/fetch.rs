@@ -0,0 +1,152 @@
+//! Fetching the remotes of tracked repositories, for `--fetch-every`.
+
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use git2::{Cred, CredentialType, FetchOptions, RemoteCallbacks, Repository};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, warn};
+
+/// How often the credentials are asked for before a fetch gives up, as
+/// libgit2 keeps asking for as long as the ones given are rejected.
+const MAX_CREDENTIAL_ATTEMPTS: u32 = 3;
+
+/// How long the watcher waits for the fetches of `--fetch-every` before it
+/// reports them as hung. libgit2 has no timeout of its own, so a remote that
+/// stops answering would otherwise keep the fetch running forever.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// What a fetch started with [`spawn_fetch_all`] reports back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FetchEvent {
+    /// The fetch is still running after the timeout. It cannot be cancelled,
+    /// so [`FetchEvent::Finished`] follows once it returns, if it ever does.
+    TimedOut,
+    /// The fetch returned, with or without errors, which are logged.
+    Finished,
+}
+
+/// The remote to fetch: the one `--remote-branch` is on, or `origin`.
+///
+/// # Arguments
+///
+/// * `remote_branch` - The remote-tracking branch given with `--remote-branch`, e.g. `upstream/main`.
+pub fn remote_name(remote_branch: Option<&str>) -> &str {
+    remote_branch.and_then(|branch| branch.split_once('/')).map_or("origin", |(remote, _)| remote)
+}
+
+/// Fetches a remote with its configured refspecs.
+///
+/// Credentials come from the SSH agent or, for HTTPS, from the git credential
+/// helper configured for the repository, as they would for `git fetch`.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `remote` - The name of the remote to fetch.
+///
+/// # Returns
+///
+/// A Result indicating whether the fetch succeeded.
+pub fn fetch(repo: &Repository, remote: &str) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    let config = repo.config()?;
+    let attempts = Cell::new(0);
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts.set(attempts.get() + 1);
+        if attempts.get() > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("the remote rejected every credential offered"));
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            return Cred::credential_helper(&config, url, username);
+        }
+        Cred::default()
+    });
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    remote.fetch(&[] as &[&str], Some(&mut options), None)
+}
+
+/// Fetches the remote of every repository, logging the ones that fail.
+///
+/// # Arguments
+///
+/// * `paths` - The paths to the repositories.
+/// * `remote` - The name of the remote to fetch.
+pub fn fetch_all(paths: &[PathBuf], remote: &str) {
+    for path in paths {
+        let result = Repository::open(path).and_then(|repo| fetch(&repo, remote));
+        match result {
+            Ok(()) => debug!(path = %path.display(), remote, "fetched"),
+            Err(e) => warn!(
+                path = %path.display(),
+                remote,
+                reason = e.message(),
+                "cannot fetch, counting the commits fetched before"
+            ),
+        }
+    }
+}
+
+/// Fetches the remote of every repository on a blocking thread, without
+/// waiting for it, and reports on `done` how the fetch went.
+///
+/// # Arguments
+///
+/// * `paths` - The paths to the repositories.
+/// * `remote` - The name of the remote to fetch.
+/// * `timeout` - How long the fetch can take before [`FetchEvent::TimedOut`] is sent.
+/// * `done` - Where the [`FetchEvent`]s are sent.
+pub fn spawn_fetch_all(paths: Vec<PathBuf>, remote: String, timeout: Duration, done: UnboundedSender<FetchEvent>) {
+    tokio::spawn(async move {
+        let mut fetch = tokio::task::spawn_blocking(move || fetch_all(&paths, &remote));
+        let result = match tokio::time::timeout(timeout, &mut fetch).await {
+            Ok(result) => result,
+            Err(_) => {
+                let _ = done.send(FetchEvent::TimedOut);
+                fetch.await
+            }
+        };
+        if let Err(e) = result {
+            error!(error = %e, "error fetching remotes");
+        }
+        let _ = done.send(FetchEvent::Finished);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn the_remote_is_taken_from_the_remote_branch() {
+        assert_eq!(remote_name(Some("upstream/release/2.0")), "upstream");
+        assert_eq!(remote_name(Some("main")), "origin");
+        assert_eq!(remote_name(None), "origin");
+    }
+
+    #[tokio::test]
+    async fn a_hung_fetch_is_reported_without_being_waited_for() {
+        // Connections are queued but never answered, until the listener goes away
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        repo.remote("origin", &format!("git://{}/repo", listener.local_addr().unwrap())).unwrap();
+
+        let (done, mut events) = tokio::sync::mpsc::unbounded_channel();
+        spawn_fetch_all(vec![dir.path().to_path_buf()], "origin".to_string(), Duration::from_millis(200), done);
+        assert_eq!(events.recv().await, Some(FetchEvent::TimedOut));
+
+        drop(listener);
+        assert_eq!(events.recv().await, Some(FetchEvent::Finished));
+    }
+}
@@ -0,0 +1,135 @@
+//! Running a command of the user's after each poll, given by `--on-update`.
+
+use std::io::ErrorKind;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// The command run after each poll, fed the status as JSON on stdin.
+#[derive(Debug, Clone)]
+pub struct UpdateHook {
+    program: String,
+    args: Vec<String>,
+    /// Set while the last run has not exited, so a slow command is not piled up.
+    running: Arc<AtomicBool>,
+}
+
+impl UpdateHook {
+    /// Parses the command line of the hook.
+    ///
+    /// The command is split into words with shell quoting rules, but no shell runs
+    /// it, so nothing in it is expanded. With `shell` it is passed to `sh -c` as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command line given with `--on-update`.
+    /// * `shell` - Whether to run the command line through `sh -c`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the hook, or an error if the command is empty or its quotes are unbalanced.
+    pub fn parse(command: &str, shell: bool) -> Result<Self, String> {
+        let mut words = match shell {
+            true => vec!["sh".to_string(), "-c".to_string(), command.to_string()],
+            false => shell_words::split(command).map_err(|e| format!("cannot parse --on-update `{}`: {}", command, e))?,
+        };
+        if words.is_empty() || words[0].is_empty() {
+            return Err("--on-update needs a command to run".to_string());
+        }
+        let program = words.remove(0);
+        Ok(UpdateHook { program, args: words, running: Arc::default() })
+    }
+
+    /// Starts the command on a task of its own with the status on stdin, logging
+    /// if it cannot be started or exits with an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status of the poll as a JSON line.
+    ///
+    /// # Returns
+    ///
+    /// The task running the command, or None if the last run is still going and
+    /// this one is skipped.
+    pub fn run(&self, status: String) -> Option<JoinHandle<()>> {
+        if self.running.swap(true, Ordering::AcqRel) {
+            debug!(program = %self.program, "on-update command still running, skipping this poll");
+            return None;
+        }
+
+        let hook = self.clone();
+        Some(tokio::spawn(async move {
+            match hook.run_to_end(&status).await {
+                Ok(exit) if exit.success() => {}
+                Ok(exit) => warn!(program = %hook.program, status = %exit, "on-update command failed"),
+                Err(e) => warn!(program = %hook.program, error = %e, "cannot run on-update command"),
+            }
+            hook.running.store(false, Ordering::Release);
+        }))
+    }
+
+    async fn run_to_end(&self, status: &str) -> std::io::Result<std::process::ExitStatus> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that does not read its input may well exit before taking all of it
+            match stdin.write_all(status.as_bytes()).await {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e),
+                _ => {}
+            }
+        }
+        child.wait().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn commands_are_split_without_a_shell() {
+        let hook = UpdateHook::parse("notify --title 'LoC updated' $HOME", false).unwrap();
+        assert_eq!(hook.program, "notify");
+        assert_eq!(hook.args, ["--title", "LoC updated", "$HOME"]);
+
+        let hook = UpdateHook::parse("jq .totals >> ~/loc.log", true).unwrap();
+        assert_eq!(hook.program, "sh");
+        assert_eq!(hook.args, ["-c", "jq .totals >> ~/loc.log"]);
+
+        assert!(UpdateHook::parse("  ", false).is_err());
+        assert!(UpdateHook::parse("notify 'unbalanced", false).is_err());
+    }
+
+    #[tokio::test]
+    async fn the_status_is_passed_on_stdin() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path().join("status.json");
+        let hook = UpdateHook::parse(&format!("tee {}", shell_words::quote(&output.to_string_lossy())), false).unwrap();
+
+        // A repository name is data for the command, never part of the command line
+        let status = "{\"repos\":[{\"repo\":\"$(touch pwned)\"}]}\n".to_string();
+        hook.run(status.clone()).unwrap().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), status);
+
+        // A failing command is only logged, and the next poll runs it again
+        let failing = UpdateHook::parse("false", false).unwrap();
+        failing.run(status.clone()).unwrap().await.unwrap();
+        assert!(failing.run(status.clone()).is_some());
+
+        // A slow command is not started again while it runs
+        let slow = UpdateHook::parse("sleep 0.2", false).unwrap();
+        let first = slow.run(status.clone()).unwrap();
+        assert!(slow.run(status.clone()).is_none());
+        first.await.unwrap();
+        assert!(slow.run(status).is_some());
+    }
+}
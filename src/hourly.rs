@@ -0,0 +1,147 @@
+//! Committed LoC of a day broken down by the hour.
+
+use std::collections::HashMap;
+
+use chrono::{Local, Timelike};
+
+use crate::store;
+use crate::{ChangeFilter, HourlyOpt, LocChange};
+
+/// The widest bar drawn by `--chart`, in characters.
+const CHART_WIDTH: i32 = 40;
+
+/// Runs the `hourly` subcommand, printing a row per hour of the requested day.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed hourly options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: HourlyOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let date = opt.date.unwrap_or_else(|| Local::now().date_naive());
+    let filter = ChangeFilter {
+        author: opt.author,
+        ..ChangeFilter::for_days(Some(date), Some(date))?
+    };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let changes = store.query_range(&filter).await?;
+    store.close().await;
+
+    print!("{}", format_histogram(&hourly_loc(&changes), opt.chart));
+    Ok(())
+}
+
+/// Spreads a day's committed LoC over the local hours they were recorded in.
+///
+/// A commit row counts in the hour of its commit. Older snapshot rows hold running
+/// totals for the day, so each contributes the growth since the previous snapshot
+/// of the same repository and author.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes of one day, ordered by timestamp.
+///
+/// # Returns
+///
+/// The committed LoC per hour, starting at midnight.
+pub fn hourly_loc(changes: &[LocChange]) -> [i32; 24] {
+    let mut hours = [0; 24];
+    let mut previous: HashMap<(&str, Option<&str>), i32> = HashMap::new();
+
+    for change in changes.iter().filter(|change| change.is_committed && change.is_total()) {
+        let loc = change.additions + change.deletions;
+        let hour = change.timestamp.with_timezone(&Local).hour() as usize;
+        if change.commit_sha.is_some() {
+            hours[hour] += loc;
+            continue;
+        }
+        let last = previous.insert((&change.repo_name, change.author.as_deref()), loc).unwrap_or(0);
+        hours[hour] += (loc - last).max(0);
+    }
+
+    hours
+}
+
+/// Formats the hourly LoC as one row per hour, with an optional bar chart.
+///
+/// # Arguments
+///
+/// * `hours` - The committed LoC per hour, starting at midnight.
+/// * `chart` - Whether to draw a bar after each count.
+pub fn format_histogram(hours: &[i32; 24], chart: bool) -> String {
+    let max = hours.iter().copied().max().unwrap_or(0);
+    let width = hours.iter().map(|loc| loc.to_string().len()).max().unwrap_or(1);
+
+    let mut output = String::new();
+    for (hour, loc) in hours.iter().enumerate() {
+        let mut line = format!("{:02}:00  {:>width$}", hour, loc, width = width);
+        if chart && *loc > 0 {
+            let bar = (loc * CHART_WIDTH + max - 1) / max;
+            line.push_str(&format!("  {}", "#".repeat(bar as usize)));
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::start_of_local_day;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn commits_and_snapshots_are_bucketed_by_hour() {
+        let day = start_of_local_day(NaiveDate::from_ymd_opt(2024, 3, 12).unwrap());
+        let committed = |author: &str, minutes: i64, additions: i32, commit_sha: Option<&str>| LocChange {
+            repo_name: "api".to_string(),
+            timestamp: day + Duration::minutes(minutes),
+            author: Some(author.to_string()),
+            author_email: None,
+            additions,
+            deletions: 0,
+            is_committed: true,
+            extension: None,
+            category: None,
+            branch: None,
+            commit_sha: commit_sha.map(str::to_string),
+            staged_additions: 0,
+            staged_deletions: 0,
+            files_changed: 0,
+            inserted_lines: 0,
+            edited_lines: 0,
+            flagged: false,
+            session_id: None,
+            is_merge: false,
+            files: Vec::new(),
+        };
+
+        let changes = [
+            // Snapshots of the running total count by their growth
+            committed("Me", 9 * 60 + 10, 10, None),
+            committed("Me", 9 * 60 + 50, 25, None),
+            // Commits count in full
+            committed("Me", 11 * 60 + 5, 15, Some("a1")),
+            committed("Other", 11 * 60 + 30, 5, Some("b2")),
+            LocChange { is_committed: false, ..committed("Me", 10 * 60, 99, None) },
+        ];
+
+        let hours = hourly_loc(&changes);
+        assert_eq!(hours[9], 25);
+        assert_eq!(hours[10], 0);
+        assert_eq!(hours[11], 20);
+        assert_eq!(hours.iter().sum::<i32>(), 45);
+
+        let histogram = format_histogram(&hours, true);
+        let lines: Vec<&str> = histogram.lines().collect();
+        assert_eq!(lines.len(), 24);
+        assert_eq!(lines[0], "00:00   0");
+        assert_eq!(lines[9], format!("09:00  25  {}", "#".repeat(40)));
+        assert_eq!(lines[11], format!("11:00  20  {}", "#".repeat(32)));
+    }
+}
@@ -0,0 +1,170 @@
+//! A self-contained HTML page of the committed LoC per day, for `report --html`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::status::DeletionsAs;
+use crate::RepoStats;
+
+/// The height of the tallest bar of the chart, in pixels.
+const CHART_HEIGHT: i32 = 160;
+/// The width of each day's bar, in pixels.
+const BAR_WIDTH: i32 = 24;
+/// The space between two bars, in pixels.
+const BAR_GAP: i32 = 8;
+/// The space under the bars for the day labels, in pixels.
+const LABEL_HEIGHT: i32 = 40;
+
+/// Kept inline so the page renders the same wherever it is opened.
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-top:1.5em}\
+th,td{padding:4px 12px;border-bottom:1px solid #ddd}\
+td{text-align:right}td:first-child,th:first-child{text-align:left}\
+tfoot td{font-weight:bold;border-top:2px solid #222}\
+svg rect{fill:#3a7d44}svg text{font-size:10px;fill:#555}";
+
+/// Writes the page: a bar chart of each day's committed LoC over every
+/// repository, then a table of the committed LoC per day and repository.
+///
+/// # Arguments
+///
+/// * `days` - The totals per repository of each local day, from `summarize_by` with `GroupBy::Day`.
+/// * `writer` - Where to write the HTML to.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub fn write_html<W: Write>(days: &BTreeMap<String, BTreeMap<String, RepoStats>>, mut writer: W) -> io::Result<()> {
+    let loc = |stats: &RepoStats| DeletionsAs::default().committed(stats);
+    let repos: BTreeSet<&String> = days.values().flat_map(|repos| repos.keys()).collect();
+    let day_totals: Vec<(&String, i32)> =
+        days.iter().map(|(day, repos)| (day, repos.values().map(loc).sum())).collect();
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Committed LoC per day</title>\n<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(writer, "<h1>Committed LoC per day</h1>")?;
+    if days.is_empty() {
+        writeln!(writer, "<p>No changes stored in this range.</p>\n</body>\n</html>")?;
+        return Ok(());
+    }
+
+    write_chart(&day_totals, &mut writer)?;
+
+    write!(writer, "<table>\n<thead>\n<tr><th>Day</th>")?;
+    for repo in &repos {
+        write!(writer, "<th>{}</th>", escape(repo))?;
+    }
+    writeln!(writer, "<th>Total</th></tr>\n</thead>\n<tbody>")?;
+    for ((day, day_repos), (_, total)) in days.iter().zip(&day_totals) {
+        write!(writer, "<tr><td>{}</td>", escape(day))?;
+        for repo in &repos {
+            write!(writer, "<td>{}</td>", day_repos.get(*repo).map(loc).unwrap_or(0))?;
+        }
+        writeln!(writer, "<td>{}</td></tr>", total)?;
+    }
+    write!(writer, "</tbody>\n<tfoot>\n<tr><td>Total</td>")?;
+    for repo in &repos {
+        let total: i32 = days.values().filter_map(|day_repos| day_repos.get(*repo)).map(loc).sum();
+        write!(writer, "<td>{}</td>", total)?;
+    }
+    let total: i32 = day_totals.iter().map(|(_, total)| total).sum();
+    writeln!(writer, "<td>{}</td></tr>\n</tfoot>\n</table>\n</body>\n</html>", total)?;
+    Ok(())
+}
+
+/// Writes the inline SVG chart with a bar per day, scaled to the busiest one.
+fn write_chart<W: Write>(day_totals: &[(&String, i32)], writer: &mut W) -> io::Result<()> {
+    let busiest = day_totals.iter().map(|(_, total)| *total).max().unwrap_or(0).max(1);
+    let width = day_totals.len() as i32 * (BAR_WIDTH + BAR_GAP) + BAR_GAP;
+    let height = CHART_HEIGHT + LABEL_HEIGHT;
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" role=\"img\" \
+         aria-label=\"Committed LoC per day\">",
+        width, height
+    )?;
+    for (index, (day, total)) in day_totals.iter().enumerate() {
+        let x = BAR_GAP + index as i32 * (BAR_WIDTH + BAR_GAP);
+        // Deletions counted as negative can bring a day below zero, which is drawn as no bar
+        let bar = total.max(&0) * CHART_HEIGHT / busiest;
+        let label_x = x + BAR_WIDTH / 2;
+        let day = escape(day);
+        writeln!(
+            writer,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"><title>{}: {} LoC</title></rect>",
+            x,
+            CHART_HEIGHT - bar,
+            BAR_WIDTH,
+            bar,
+            day,
+            total
+        )?;
+        writeln!(
+            writer,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" transform=\"rotate(-45 {} {})\">{}</text>",
+            label_x,
+            CHART_HEIGHT + 12,
+            label_x,
+            CHART_HEIGHT + 12,
+            day
+        )?;
+    }
+    writeln!(writer, "</svg>")
+}
+
+/// Escapes the characters HTML gives a meaning to, for repository names and the like.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{start_of_local_day, summarize_by, GroupBy};
+    use crate::store::{SqliteStore, Store};
+    use crate::{changes_from_stats, ChangeFilter};
+    use chrono::{Duration, Local};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn the_page_lists_each_repository_and_day_with_its_totals() {
+        let store = SqliteStore::memory().await;
+        let today = start_of_local_day(Local::now().date_naive()) + Duration::hours(12);
+        let yesterday = today - Duration::days(1);
+        let rows = [("api", yesterday, "a1", 30, 10), ("api", today, "b2", 5, 0), ("<web>", today, "c3", 12, 3)];
+        for (repo, at, sha, additions, deletions) in rows {
+            let stats = RepoStats::with_commit(sha, at, additions, deletions);
+            for change in &changes_from_stats(repo, "Me", None, &stats, at) {
+                store.store_change(change).await.unwrap();
+            }
+        }
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.html");
+        write_html(&summarize_by(&changes, GroupBy::Day, 0), std::fs::File::create(&path).unwrap()).unwrap();
+
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<th>api</th>") && html.contains("<th>&lt;web&gt;</th>"));
+        let day = |at: chrono::DateTime<chrono::Utc>| at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+        assert!(html.contains(&format!("<tr><td>{}</td><td>0</td><td>40</td><td>40</td></tr>", day(yesterday))));
+        assert!(html.contains(&format!("<tr><td>{}</td><td>15</td><td>5</td><td>20</td></tr>", day(today))));
+        assert!(html.contains("<tr><td>Total</td><td>15</td><td>45</td><td>60</td></tr>"));
+        assert!(html.contains("<svg") && html.contains("<title>"));
+        // Nothing is loaded from elsewhere
+        assert!(!html.contains("<script") && !html.contains("<link") && !html.contains("src="));
+    }
+}
@@ -0,0 +1,79 @@
+//! Slowing the watcher down while nothing is being worked on.
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::info;
+
+/// How often an idle watcher polls at most, gathering the events in between.
+pub const IDLE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Tells from the outcome of each poll whether the tracked work has gone idle.
+#[derive(Debug, Clone)]
+pub struct IdleTracker {
+    /// How long the stats have to stay the same before going idle.
+    idle_after: Duration,
+    /// When a poll last found the stats changed, or when tracking started.
+    last_change: DateTime<Utc>,
+    /// Whether polling is slowed down.
+    idle: bool,
+}
+
+impl IdleTracker {
+    /// Creates a tracker going idle after `idle_minutes` without a change, counted from `now`.
+    pub fn new(idle_minutes: u32, now: DateTime<Utc>) -> Self {
+        IdleTracker { idle_after: Duration::minutes(idle_minutes.into()), last_change: now, idle: false }
+    }
+
+    /// Whether polling is slowed down.
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Records the outcome of a poll, logging when the state flips.
+    ///
+    /// # Arguments
+    ///
+    /// * `changed` - Whether the poll found new commits or working tree changes.
+    /// * `now` - The time of the poll.
+    ///
+    /// # Returns
+    ///
+    /// Whether the tracked work is idle after the poll.
+    pub fn record_poll(&mut self, changed: bool, now: DateTime<Utc>) -> bool {
+        if changed {
+            self.last_change = now;
+            if self.idle {
+                self.idle = false;
+                info!("active again: polling on every event");
+            }
+        } else if !self.idle && now - self.last_change >= self.idle_after {
+            self.idle = true;
+            info!(
+                idle_minutes = self.idle_after.num_minutes(),
+                poll_secs = IDLE_POLL_INTERVAL.as_secs(),
+                "idle: no changes for a while, polling at most once a minute until something changes"
+            );
+        }
+        self.idle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_without_changes_go_idle_until_a_change() {
+        let start = Utc::now();
+        let mut tracker = IdleTracker::new(15, start);
+
+        assert!(!tracker.record_poll(false, start + Duration::minutes(5)));
+        assert!(!tracker.record_poll(false, start + Duration::minutes(14)));
+        assert!(tracker.record_poll(false, start + Duration::minutes(15)));
+        assert!(tracker.record_poll(false, start + Duration::minutes(40)));
+
+        // A change wakes it up and restarts the wait
+        assert!(!tracker.record_poll(true, start + Duration::minutes(41)));
+        assert!(!tracker.record_poll(false, start + Duration::minutes(50)));
+        assert!(tracker.record_poll(false, start + Duration::minutes(56)));
+    }
+}
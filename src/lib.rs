@@ -0,0 +1,4457 @@
+//! Tracking the lines of code written in git repositories.
+//!
+//! The command line is a thin wrapper around [`run`]. Other frontends can collect
+//! the stats of a repository with [`collect_stats`], turn them into rows with
+//! [`changes_from_stats`] and keep them in a [`Store`].
+
+use std::path::{Path, PathBuf};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use git2::{Delta, Diff, DiffDelta, DiffFindOptions, DiffHunk, DiffOptions, ErrorCode, Oid, Repository, Revwalk, Sort, Status, StatusOptions, Time};
+use chrono::{DateTime, FixedOffset, Utc, Local, NaiveDate, TimeZone};
+use structopt::StructOpt;
+use regex::Regex;
+use notify::{DebouncedEvent, Watcher, RecursiveMode, watcher};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use status::{DeletionsAs, DisplayOptions, OutputFormat};
+use tracing::{debug, error, info, info_span, warn, Instrument, Level};
+
+pub mod author_map;
+mod authors;
+mod backfill;
+mod compare;
+mod config;
+mod control;
+mod discover;
+mod export;
+mod fetch;
+mod hook;
+mod hourly;
+mod html;
+mod idle;
+mod lifetime;
+mod metrics;
+mod milestone;
+mod report;
+mod rollup;
+mod status;
+pub mod store;
+mod streak;
+mod top_files;
+mod tui;
+mod velocity;
+
+pub use rollup::DailySummary;
+pub use store::Store;
+
+#[derive(StructOpt)]
+#[structopt(name = "git-loc-tracker", about = "Track LoC changes in git repositories")]
+// Parsed once at startup, so the size of the watch options does not matter
+#[allow(clippy::large_enum_variant)]
+pub enum Opt {
+    /// Watch repositories and record LoC changes as they happen.
+    Watch(WatchOpt),
+
+    /// Print per-repository totals from the stored changes.
+    Report(ReportOpt),
+
+    /// Aggregate stored changes into the daily summary table.
+    Rollup(RollupOpt),
+
+    /// Write the stored changes as CSV.
+    Export(ExportOpt),
+
+    /// Import the committed changes of past days from the repositories' history.
+    Backfill(BackfillOpt),
+
+    /// Print how many days in a row an author has committed code.
+    Streak(StreakOpt),
+
+    /// Print the committed LoC of a day per hour.
+    Hourly(HourlyOpt),
+
+    /// Print the committed LoC of each repository over everything stored.
+    Lifetime(LifetimeOpt),
+
+    /// Compare the committed LoC of this week, or another period, with the one before.
+    Compare(CompareOpt),
+
+    /// Print the files with the most committed LoC changed.
+    TopFiles(TopFilesOpt),
+
+    /// List the authors who committed to a repository, to pick `--author` from.
+    Authors(AuthorsOpt),
+}
+
+#[derive(StructOpt)]
+pub struct WatchOpt {
+    /// Paths to the git repositories to track.
+    #[structopt(parse(from_os_str))]
+    paths: Vec<PathBuf>,
+
+    /// Also track the repository at a path under the given name, e.g. `--repo work-api=~/work/api`;
+    /// repeatable. Repositories sharing a directory name are otherwise told apart by their parent.
+    #[structopt(long = "repo", number_of_values = 1, parse(try_from_str = parse_named_repo))]
+    named_repos: Vec<(String, PathBuf)>,
+
+    /// Also track every repository found below this directory; repeat the flag for each root.
+    #[structopt(long, number_of_values = 1, parse(from_os_str))]
+    discover: Vec<PathBuf>,
+
+    /// How many directory levels below each `--discover` root are searched.
+    #[structopt(long, default_value = "3")]
+    discover_depth: usize,
+
+    /// Also search inside the repositories found by `--discover` for nested ones.
+    #[structopt(long)]
+    include_nested: bool,
+    
+    /// The authors whose changes will be tracked; repeat the flag for each author.
+    /// Uncommitted changes are attributed to the first one. Without any author, the
+    /// user.name and user.email of each repository's git config are tracked.
+    #[structopt(short, long = "author", number_of_values = 1)]
+    authors: Vec<String>,
+
+    /// Author emails whose changes will be tracked, matched in addition to the names.
+    #[structopt(long = "author-email", number_of_values = 1)]
+    author_emails: Vec<String>,
+
+    /// TOML or JSON file mapping each canonical author name to the names and emails it
+    /// also commits under.
+    #[structopt(long, parse(from_os_str))]
+    author_map: Option<PathBuf>,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL [default: loc_stats.db].
+    #[structopt(long)]
+    db_path: Option<String>,
+
+    /// Compute and print the stats without writing to the database, which is only read from; nothing is stored.
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Keep SQLite's default rollback journal instead of write-ahead logging, e.g. on a networked filesystem.
+    #[structopt(long)]
+    no_wal: bool,
+
+    /// Every this many minutes, write a timestamped copy of the SQLite database to `--snapshot-dir`.
+    #[structopt(long, parse(try_from_str = parse_snapshot_minutes))]
+    snapshot_db_every: Option<u32>,
+
+    /// The directory the snapshots are written to [default: the database's directory].
+    #[structopt(long, parse(from_os_str), requires = "snapshot-db-every")]
+    snapshot_dir: Option<PathBuf>,
+
+    /// TOML file with defaults for the paths, authors, db_path, interval_secs and exclude flags,
+    /// and the labels and colors of repositories [default: ~/.config/devmetrics/config.toml].
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Output format of the per-poll status: `text`, or `json` for one object per line.
+    #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+    format: OutputFormat,
+
+    /// How long the watcher waits for file events to settle before polling, in seconds [default: 300].
+    #[structopt(long, parse(try_from_str = parse_interval_secs))]
+    interval_secs: Option<u64>,
+
+    /// Poll at most once a minute after this many minutes without new commits or
+    /// working tree changes, until a poll finds one.
+    #[structopt(long)]
+    idle_minutes: Option<u32>,
+
+    /// Count merge commits by diffing them against their first parent; they are skipped otherwise.
+    #[structopt(long)]
+    count_merges: bool,
+
+    /// Break changes down by file extension in the printout and the stored rows.
+    #[structopt(long)]
+    by_language: bool,
+
+    /// Split changes into code and docs, by file extension, in the printout and the stored rows.
+    #[structopt(long)]
+    by_category: bool,
+
+    /// An extension of the files counted as docs rather than code by `--by-category`; repeatable
+    /// [default: md, rst, txt, adoc].
+    #[structopt(long = "doc-extension", number_of_values = 1)]
+    doc_extensions: Vec<String>,
+
+    /// Split the committed additions into new code, from hunks that only add lines, and edits
+    /// of existing code, from hunks that also delete some.
+    #[structopt(long)]
+    by_hunk: bool,
+
+    /// Store a row on every poll, even when the stats have not changed since the last one.
+    #[structopt(long)]
+    store_all: bool,
+
+    /// Warn about and skip paths that are not git repositories instead of exiting.
+    #[structopt(long)]
+    skip_invalid: bool,
+
+    /// Use the repository paths as given instead of resolving symlinks to the real directory.
+    #[structopt(long)]
+    no_canonicalize: bool,
+
+    /// Tag the rows written by this run with this id instead of a random UUID, e.g. in tests.
+    #[structopt(long)]
+    session_id: Option<String>,
+
+    /// Glob of paths to leave out of both committed and pending counts, e.g. 'target/**'; repeatable.
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Only watch and count this directory of each repository, e.g. `src`; repeatable.
+    #[structopt(long = "subpath", number_of_values = 1)]
+    subpaths: Vec<String>,
+
+    /// Skip commits whose summary line matches this regular expression, e.g. '^wip'.
+    #[structopt(long, parse(try_from_str = Regex::new))]
+    skip_message_regex: Option<Regex>,
+
+    /// Date commits in this fixed offset, e.g. `UTC` or `+02:00`, instead of the offset they were made in.
+    #[structopt(long, parse(try_from_str = parse_timezone))]
+    timezone: Option<FixedOffset>,
+
+    /// The hour a day starts at, so work until then counts for the day before, e.g. 4 for a 4am rollover.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Count the lines of binary files, which are left out by default.
+    #[structopt(long)]
+    count_binary: bool,
+
+    /// Count the commits of this local branch instead of the checked out one, e.g. `main`.
+    #[structopt(long)]
+    branch: Option<String>,
+
+    /// Count the commits of this remote-tracking branch instead, e.g. `origin/main`, to follow
+    /// the commits of others in a repository you mostly review.
+    #[structopt(long, conflicts_with = "branch")]
+    remote_branch: Option<String>,
+
+    /// Every this many minutes, fetch the remote of `--remote-branch` (or `origin`) in every
+    /// repository and poll again.
+    #[structopt(long, parse(try_from_str = parse_fetch_minutes))]
+    fetch_every: Option<u32>,
+
+    /// Don't count lines whose only change is whitespace, such as reindented code.
+    #[structopt(long)]
+    ignore_whitespace: bool,
+
+    /// Only count significant lines: blank lines and lines holding nothing but a
+    /// `//`, `#` or `--` comment are left out.
+    #[structopt(long)]
+    significant_lines_only: bool,
+
+    /// Count a submodule whose checked out commit moved as pending changes; submodules
+    /// are left out of the pending counts otherwise.
+    #[structopt(long)]
+    count_submodules: bool,
+
+    /// Stop looking for today's commits after visiting this many, newest first; a guard for huge histories.
+    #[structopt(long)]
+    max_commits: Option<usize>,
+
+    /// Flag commits adding more than this many net LoC, such as vendored imports. They are
+    /// stored with `flagged` set and left out of the totals, the daily goal and the streak.
+    #[structopt(long, value_name = "N")]
+    flag_large_commit: Option<u32>,
+
+    /// Serve Prometheus metrics of the latest poll on `/metrics` at this host:port.
+    #[structopt(long)]
+    metrics_addr: Option<String>,
+
+    /// Answer line commands such as `stats`, replied to with the latest poll as JSON,
+    /// on a Unix socket created at this path.
+    #[structopt(long, parse(from_os_str))]
+    control_socket: Option<PathBuf>,
+
+    /// Run this command after each poll, with the stats as a JSON line on stdin. It is
+    /// split into words like a shell would but not run by one, unless `--on-update-shell` is given.
+    #[structopt(long)]
+    on_update: Option<String>,
+
+    /// Run the `--on-update` command with `sh -c`, so pipes and redirections work.
+    #[structopt(long, requires = "on-update")]
+    on_update_shell: bool,
+
+    /// Show a live dashboard instead of printing a status after each poll; `q` quits.
+    #[structopt(long, conflicts_with = "format")]
+    tui: bool,
+
+    /// Poll and store every repository a single time, print the status and exit.
+    #[structopt(long, conflicts_with_all = &["tui", "metrics-addr"])]
+    once: bool,
+
+    /// Don't print the status after each poll; stats are still stored and logs still printed.
+    #[structopt(long, conflicts_with = "tui")]
+    quiet: bool,
+
+    /// Print only a timestamped line with the total after each poll, and how much it moved.
+    #[structopt(long, conflicts_with_all = &["tui", "format", "quiet"])]
+    compact: bool,
+
+    /// Add the net (additions minus deletions) and churn (additions plus deletions) to the printout.
+    #[structopt(long)]
+    show_net: bool,
+
+    /// Add the number of files changed to the printout.
+    #[structopt(long)]
+    show_files: bool,
+
+    /// How deleted lines count towards the LoC shown, the daily goal and milestones:
+    /// `positive` like added ones, `negative` against them, or `ignore`d.
+    #[structopt(long, default_value = "positive", possible_values = &["positive", "negative", "ignore"])]
+    deletions_as: DeletionsAs,
+
+    /// Never color the printout; colors are also left out when stdout is not a
+    /// terminal or `NO_COLOR` is set.
+    #[structopt(long)]
+    no_color: bool,
+
+    /// Show the day's committed LoC as progress towards this many, e.g. `800`.
+    #[structopt(long)]
+    daily_goal: Option<u32>,
+
+    /// Show a desktop notification each time the day's committed LoC crosses a multiple of this.
+    #[structopt(long)]
+    notify_milestone: Option<u32>,
+
+    /// Post each milestone reached with `--notify-milestone` to this Slack incoming webhook URL.
+    #[structopt(long, requires = "notify-milestone")]
+    slack_webhook: Option<String>,
+
+    /// The most verbose log level to print: error, warn, info, debug or trace.
+    #[structopt(long, default_value = "info")]
+    log_level: Level,
+
+    /// Print logs as JSON lines instead of the compact human-readable format.
+    #[structopt(long)]
+    log_json: bool,
+
+    /// Before watching, store the commits made since the newest stored row, each with its commit time.
+    #[structopt(long)]
+    catch_up: bool,
+
+    /// Delete the stored rows older than this many days, on startup and then once a day.
+    #[structopt(long)]
+    retain_days: Option<u32>,
+
+    /// Roll the days deleted by `--retain-days` up into the daily summaries first.
+    #[structopt(long, requires = "retain-days")]
+    rollup_pruned: bool,
+
+    /// Stop tracking a repository for this run, by name or path, without removing it; repeatable.
+    #[structopt(long, number_of_values = 1)]
+    disable: Vec<String>,
+
+    /// Track a repository the config file disables with `enabled = false`, by name or path; repeatable.
+    #[structopt(long, number_of_values = 1)]
+    enable: Vec<String>,
+
+    /// The labels, colors and tracking of repositories, only set from the config file.
+    #[structopt(skip)]
+    repo_configs: Vec<config::RepoConfig>,
+}
+
+impl WatchOpt {
+    /// Fills in the flags not given on the command line from the config file.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `config` - The parsed config file.
+    /// 
+    /// # Returns
+    /// 
+    /// A Result indicating whether the merged options are complete and valid.
+    fn apply_config(&mut self, config: config::Config) -> Result<(), String> {
+        if self.paths.is_empty() {
+            self.paths = config.paths;
+        }
+        if self.authors.is_empty() && self.author_emails.is_empty() {
+            self.authors = config.authors;
+        }
+        if self.db_path.is_none() {
+            self.db_path = config.db_path;
+        }
+        if self.interval_secs.is_none() {
+            self.interval_secs = config.interval_secs.map(|secs| parse_interval_secs(&secs.to_string())).transpose()?;
+        }
+        if self.exclude.is_empty() {
+            self.exclude = config.exclude;
+        }
+        if self.daily_goal.is_none() {
+            self.daily_goal = config.daily_goal;
+        }
+        if self.doc_extensions.is_empty() {
+            self.doc_extensions = config.doc_extensions;
+        }
+        if self.retain_days.is_none() {
+            self.retain_days = config.retain_days;
+        }
+        self.repo_configs = config.repos;
+        Ok(())
+    }
+
+    fn db_path(&self) -> &str {
+        self.db_path.as_deref().unwrap_or("loc_stats.db")
+    }
+
+    fn interval_secs(&self) -> u64 {
+        self.interval_secs.unwrap_or(300)
+    }
+
+    fn author_filter(&self) -> Result<AuthorFilter, Box<dyn std::error::Error>> {
+        let aliases = match &self.author_map {
+            Some(path) => author_map::AuthorMap::load(path)?,
+            None => Default::default(),
+        };
+        Ok(AuthorFilter::new(self.authors.clone(), self.author_emails.clone(), aliases))
+    }
+
+    fn collect_options(&self) -> CollectOptions {
+        CollectOptions {
+            count_merges: self.count_merges,
+            by_language: self.by_language,
+            by_category: self.by_category,
+            doc_extensions: self
+                .doc_extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+            by_hunk: self.by_hunk,
+            exclude: self.exclude.clone(),
+            subpaths: self.subpaths.iter().map(|subpath| subpath.trim_end_matches('/').to_string()).collect(),
+            skip_message: self.skip_message_regex.clone(),
+            timezone: self.timezone,
+            day_start_hour: self.day_start_hour,
+            count_binary: self.count_binary,
+            branch: self.branch.clone(),
+            remote_branch: self.remote_branch.clone(),
+            ignore_whitespace: self.ignore_whitespace,
+            significant_lines_only: self.significant_lines_only,
+            count_submodules: self.count_submodules,
+            max_commits: self.max_commits,
+            flag_large_commit: self.flag_large_commit,
+            count_root_commits: false,
+        }
+    }
+
+    /// The options of the per-poll status, with a line per author when several are tracked.
+    fn display_options(&self, authors: &AuthorFilter) -> DisplayOptions {
+        DisplayOptions {
+            format: self.format,
+            by_language: self.by_language,
+            by_category: self.by_category,
+            by_hunk: self.by_hunk,
+            by_author: authors.identities().count() > 1,
+            show_net: self.show_net,
+            show_files: self.show_files,
+            daily_goal: self.daily_goal,
+            color: status::use_color(self.no_color),
+            deletions_as: self.deletions_as,
+            // Set once the repositories are named
+            repo_styles: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(StructOpt)]
+pub struct BackfillOpt {
+    /// Paths to the git repositories to import.
+    #[structopt(parse(from_os_str))]
+    paths: Vec<PathBuf>,
+
+    /// Also import the repository at a path under the given name, as with `watch`; repeatable.
+    #[structopt(long = "repo", number_of_values = 1, parse(try_from_str = parse_named_repo))]
+    named_repos: Vec<(String, PathBuf)>,
+
+    /// The authors whose commits will be imported; repeat the flag for each author.
+    #[structopt(short, long = "author", number_of_values = 1, required_unless = "author-emails")]
+    authors: Vec<String>,
+
+    /// Author emails whose commits will be imported, matched in addition to the names.
+    #[structopt(long = "author-email", number_of_values = 1)]
+    author_emails: Vec<String>,
+
+    /// TOML or JSON file mapping each canonical author name to the names and emails it
+    /// also commits under.
+    #[structopt(long, parse(from_os_str))]
+    author_map: Option<PathBuf>,
+
+    /// How many days before today to import.
+    #[structopt(long, default_value = "7")]
+    days: u32,
+
+    /// Count merge commits by diffing them against their first parent; they are skipped otherwise.
+    #[structopt(long)]
+    count_merges: bool,
+
+    /// Split the committed additions into new code and edits of existing code, like `watch --by-hunk`.
+    #[structopt(long)]
+    by_hunk: bool,
+
+    /// Glob of paths to leave out of the counts, e.g. 'target/**'; repeatable.
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Skip commits whose summary line matches this regular expression, e.g. '^wip'.
+    #[structopt(long, parse(try_from_str = Regex::new))]
+    skip_message_regex: Option<Regex>,
+
+    /// Date commits in this fixed offset, e.g. `UTC` or `+02:00`, instead of the offset they were made in.
+    #[structopt(long, parse(try_from_str = parse_timezone))]
+    timezone: Option<FixedOffset>,
+
+    /// The hour a day starts at, so work until then counts for the day before, e.g. 4 for a 4am rollover.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Count the lines of binary files, which are left out by default.
+    #[structopt(long)]
+    count_binary: bool,
+
+    /// Count the commits of this local branch instead of the checked out one, e.g. `main`.
+    #[structopt(long)]
+    branch: Option<String>,
+
+    /// Don't count lines whose only change is whitespace, such as reindented code.
+    #[structopt(long)]
+    ignore_whitespace: bool,
+
+    /// Only count significant lines: blank lines and lines holding nothing but a
+    /// `//`, `#` or `--` comment are left out.
+    #[structopt(long)]
+    significant_lines_only: bool,
+
+    /// Flag commits adding more than this many net LoC, as with `watch`.
+    #[structopt(long, value_name = "N")]
+    flag_large_commit: Option<u32>,
+
+    /// Use the repository paths as given instead of resolving symlinks to the real directory.
+    #[structopt(long)]
+    no_canonicalize: bool,
+
+    /// Tag the rows written by this run with this id instead of a random UUID, e.g. in tests.
+    #[structopt(long)]
+    session_id: Option<String>,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct StreakOpt {
+    /// The author whose streak is counted.
+    #[structopt(short, long)]
+    author: String,
+
+    /// The hour a day starts at, so work until then counts for the day before, as with `watch`.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct HourlyOpt {
+    /// The day to break down, as YYYY-MM-DD in local time; defaults to today.
+    #[structopt(long)]
+    date: Option<NaiveDate>,
+
+    /// Only include changes recorded for this author.
+    #[structopt(short, long)]
+    author: Option<String>,
+
+    /// Draw a bar chart next to the counts.
+    #[structopt(long)]
+    chart: bool,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct LifetimeOpt {
+    /// Only include changes recorded for this author.
+    #[structopt(short, long)]
+    author: Option<String>,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct CompareOpt {
+    /// The periods to compare: the current `day`, `week` or `month` so far with the whole one before.
+    #[structopt(long, default_value = "week", possible_values = &["day", "week", "month"])]
+    period: report::GroupBy,
+
+    /// Only include changes recorded for this author.
+    #[structopt(short, long)]
+    author: Option<String>,
+
+    /// The hour a day starts at, so work until then counts for the day before, as with `watch`.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct AuthorsOpt {
+    /// Path to the git repository to look at.
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// How many days back to look for commits.
+    #[structopt(long, default_value = "90")]
+    days: u32,
+}
+
+#[derive(StructOpt)]
+pub struct TopFilesOpt {
+    /// Only include commits recorded for this author.
+    #[structopt(short, long)]
+    author: Option<String>,
+
+    /// First day to include as YYYY-MM-DD in local time, or the instant to start at,
+    /// given like the `--since` of `report`.
+    #[structopt(long)]
+    since: Option<report::TimeBound>,
+
+    /// Last day to include as YYYY-MM-DD in local time, or the instant to stop before.
+    #[structopt(long)]
+    until: Option<report::TimeBound>,
+
+    /// How many files to print.
+    #[structopt(short = "n", long, default_value = "10")]
+    limit: usize,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+/// Parses a fixed UTC offset such as `+02:00`, also accepting `UTC` and `Z`.
+fn parse_timezone(value: &str) -> Result<FixedOffset, String> {
+    if value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return Ok(FixedOffset::east_opt(0).expect("zero is a valid offset"));
+    }
+    value
+        .parse()
+        .map_err(|_| format!("`{}` is not a UTC offset like +02:00", value))
+}
+
+/// Parses the hour days start at, from 0 to 23.
+fn parse_day_start_hour(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(hour) if hour < 24 => Ok(hour),
+        _ => Err(format!("`{}` is not an hour from 0 to 23", value)),
+    }
+}
+
+/// Parses the minutes between database snapshots, rejecting zero.
+fn parse_snapshot_minutes(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(0) => Err("snapshots must be at least 1 minute apart".to_string()),
+        Ok(minutes) => Ok(minutes),
+        Err(_) => Err(format!("`{}` is not a whole number of minutes", value)),
+    }
+}
+
+/// Parses the minutes between fetches, rejecting zero.
+fn parse_fetch_minutes(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(0) => Err("fetches must be at least 1 minute apart".to_string()),
+        Ok(minutes) => Ok(minutes),
+        Err(_) => Err(format!("`{}` is not a whole number of minutes", value)),
+    }
+}
+
+/// Parses a `name=path` pair naming a repository.
+fn parse_named_repo(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok((name.to_string(), PathBuf::from(path))),
+        _ => Err(format!("`{}` is not a repository like name=path", value)),
+    }
+}
+
+/// Parses the watch interval, rejecting values below one second.
+fn parse_interval_secs(value: &str) -> Result<u64, String> {
+    let secs: u64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a whole number of seconds", value))?;
+    if secs < 1 {
+        return Err("the interval must be at least 1 second".to_string());
+    }
+    Ok(secs)
+}
+
+#[derive(StructOpt)]
+pub struct ReportOpt {
+    /// Only include changes recorded for this author.
+    #[structopt(short, long)]
+    author: Option<String>,
+
+    /// First day to include as YYYY-MM-DD in local time, or the instant to start at:
+    /// an RFC 3339 timestamp, a local `2024-03-12T13:00` or `13:00` for today.
+    #[structopt(long)]
+    since: Option<report::TimeBound>,
+
+    /// Last day to include as YYYY-MM-DD in local time, or the instant to stop before,
+    /// given like `--since`.
+    #[structopt(long)]
+    until: Option<report::TimeBound>,
+
+    /// Split the totals by local `day`, ISO `week` or `month`.
+    #[structopt(long, possible_values = &["day", "week", "month"])]
+    group_by: Option<report::GroupBy>,
+
+    /// Average the committed LoC of each day of the week, Monday to Sunday, over the range.
+    #[structopt(long, conflicts_with = "group-by")]
+    by_weekday: bool,
+
+    /// Write a self-contained HTML page with a chart and a table of the committed LoC per day to this file.
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["group-by", "by-weekday"])]
+    html: Option<PathBuf>,
+
+    /// The hour a day starts at, so work until then counts for the day before, as with `watch`.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct RollupOpt {
+    /// First day to roll up, as YYYY-MM-DD in local time; defaults to the first stored day.
+    #[structopt(long)]
+    since: Option<NaiveDate>,
+
+    /// Last day to roll up, as YYYY-MM-DD in local time; defaults to today.
+    #[structopt(long)]
+    until: Option<NaiveDate>,
+
+    /// The hour a day starts at, so work until then counts for the day before, as with `watch`.
+    #[structopt(long, default_value = "0", parse(try_from_str = parse_day_start_hour))]
+    day_start_hour: u32,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+#[derive(StructOpt)]
+pub struct ExportOpt {
+    /// First day to include as YYYY-MM-DD in local time, or the instant to start at,
+    /// given like `report --since`.
+    #[structopt(long)]
+    since: Option<report::TimeBound>,
+
+    /// Last day to include as YYYY-MM-DD in local time, or the instant to stop before.
+    #[structopt(long)]
+    until: Option<report::TimeBound>,
+
+    /// Only include changes of this repository.
+    #[structopt(long)]
+    repo: Option<String>,
+
+    /// File to write the CSV to; defaults to stdout.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Path to the SQLite database used to store changes, or a `postgres://` URL.
+    #[structopt(long, default_value = "loc_stats.db")]
+    db_path: String,
+}
+
+/// A struct representing a line of code change in a repository.
+///
+/// Committed rows with a `commit_sha` count a single commit. Pending rows, and
+/// committed rows stored before commits were recorded one by one, are snapshots
+/// of the running totals of their day.
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize)]
+pub struct LocChange {
+    pub repo_name: String,
+    pub timestamp: DateTime<Utc>,
+    /// The name the change is counted under.
+    pub author: Option<String>,
+    /// The email of the commit's author, or of the person whose pending work this is.
+    pub author_email: Option<String>,
+    pub additions: i32,
+    pub deletions: i32,
+    pub is_committed: bool,
+    /// The file extension this row is limited to, or None for the repository total.
+    pub extension: Option<String>,
+    /// The category of `--by-category` this row is limited to, `code` or `docs`, or None
+    /// for the repository total.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// The checked out branch, or the short commit SHA when HEAD is detached.
+    pub branch: Option<String>,
+    /// The commit a committed row counts, or None for a snapshot row.
+    pub commit_sha: Option<String>,
+    /// The part of a pending row's additions already staged in the index.
+    pub staged_additions: i32,
+    /// The part of a pending row's deletions already staged in the index.
+    pub staged_deletions: i32,
+    /// The number of files the row's additions and deletions were made in.
+    pub files_changed: i32,
+    /// The part of a committed row's additions made in hunks that only add lines, see `--by-hunk`.
+    #[serde(default)]
+    pub inserted_lines: i32,
+    /// The part of a committed row's additions made in hunks that also delete lines.
+    #[serde(default)]
+    pub edited_lines: i32,
+    /// Whether the row's commit was too large to be believed, see `--flag-large-commit`.
+    /// Flagged rows are kept but left out of every total.
+    #[serde(default)]
+    pub flagged: bool,
+    /// The run of the tool that wrote the row, a UUID unless given with `--session-id`.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Whether the row's commit is a merge, counted against its first parent with `--count-merges`.
+    #[serde(default)]
+    pub is_merge: bool,
+    /// The counts per file of a commit's repository total row, stored in a table
+    /// of their own; empty on every other row.
+    #[sqlx(skip)]
+    #[serde(skip)]
+    pub files: Vec<FileChange>,
+}
+
+impl LocChange {
+    /// Whether the row holds the repository total rather than a part of its
+    /// breakdown by extension or category.
+    pub fn is_total(&self) -> bool {
+        self.extension.is_none() && self.category.is_none()
+    }
+}
+
+/// The lines a commit changed in a single file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    /// The path the file had before the commit renamed it.
+    pub old_path: Option<String>,
+    pub additions: i32,
+    pub deletions: i32,
+}
+
+/// A struct to hold statistics about a repository's changes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepoStats {
+    pub committed_additions: i32,
+    pub committed_deletions: i32,
+    pub pending_additions: i32,
+    pub pending_deletions: i32,
+    /// The part of the pending counts that is staged in the index; the rest is unstaged.
+    pub staged_additions: i32,
+    pub staged_deletions: i32,
+    /// The files changed by the committed counts, summed over commits.
+    pub committed_files: i32,
+    /// The committed additions made in hunks that only add lines, with `--by-hunk`.
+    pub committed_inserted: i32,
+    /// The committed additions made in hunks that also delete lines, rewriting existing code.
+    pub committed_edited: i32,
+    /// The files with staged or unstaged changes, each counted once.
+    pub pending_files: i32,
+    /// The same stats per file extension, only collected with `--by-language`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, RepoStats>,
+    /// The same stats for code and for docs, only collected with `--by-category`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, RepoStats>,
+    /// The commits summed into the committed counts, each stored as its own row.
+    #[serde(skip)]
+    pub commits: Vec<CommitStats>,
+    /// The email of the person whose pending work is counted.
+    #[serde(skip)]
+    pub pending_email: Option<String>,
+}
+
+/// The committed counts of a single commit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitStats {
+    pub sha: String,
+    /// The email of the commit's author.
+    pub author_email: Option<String>,
+    /// The commit time.
+    pub timestamp: DateTime<Utc>,
+    pub stats: RepoStats,
+    /// The counts of each file the commit changed.
+    pub files: Vec<FileChange>,
+    /// Whether the commit is too large to count, in which case it is stored but
+    /// left out of the committed totals.
+    pub flagged: bool,
+    /// Whether the commit is a merge, counted as its diff against the first parent
+    /// with `--count-merges`.
+    pub is_merge: bool,
+}
+
+impl RepoStats {
+    /// Lines added minus lines deleted, committed and pending together; negative
+    /// when more was removed than written.
+    pub fn net(&self) -> i32 {
+        self.committed_additions + self.pending_additions - self.committed_deletions - self.pending_deletions
+    }
+
+    /// Lines added, committed and pending together.
+    pub fn additions(&self) -> i32 {
+        self.committed_additions + self.pending_additions
+    }
+
+    /// Lines deleted, committed and pending together.
+    pub fn deletions(&self) -> i32 {
+        self.committed_deletions + self.pending_deletions
+    }
+
+    /// Lines added plus lines deleted, committed and pending together.
+    pub fn churn(&self) -> i32 {
+        self.committed_additions + self.pending_additions + self.committed_deletions + self.pending_deletions
+    }
+
+    /// Pending lines changed in the working tree but not staged yet.
+    pub fn unstaged_loc(&self) -> i32 {
+        self.pending_additions + self.pending_deletions - self.staged_additions - self.staged_deletions
+    }
+
+    /// Adds the staged and unstaged counts of the working tree to the pending
+    /// totals and their breakdown.
+    fn add_pending(&mut self, staged: DiffCounts, unstaged: DiffCounts) {
+        self.staged_additions += staged.additions;
+        self.staged_deletions += staged.deletions;
+        self.pending_additions += staged.additions + unstaged.additions;
+        self.pending_deletions += staged.deletions + unstaged.deletions;
+        add_pending_buckets(&mut self.extensions, staged.by_extension, unstaged.by_extension);
+        add_pending_buckets(&mut self.categories, staged.by_category, unstaged.by_category);
+        // A file with both staged and unstaged edits is still one file, as far as they were kept
+        let files: BTreeSet<&String> = staged.files.iter().chain(&unstaged.files).map(|file| &file.path).collect();
+        self.pending_files += files.len() as i32 + staged.more_files + unstaged.more_files;
+        for file in files {
+            if let Some(bucket) = self.extensions.get_mut(&path_extension(Path::new(file))) {
+                bucket.pending_files += 1;
+            }
+            let category = match staged.doc_files.contains(file) || unstaged.doc_files.contains(file) {
+                true => DOCS_CATEGORY,
+                false => CODE_CATEGORY,
+            };
+            if let Some(bucket) = self.categories.get_mut(category) {
+                bucket.pending_files += 1;
+            }
+        }
+    }
+
+    /// Adds the counts of a commit to the committed totals and their breakdown.
+    fn add_committed(&mut self, counts: DiffCounts) {
+        self.committed_additions += counts.additions;
+        self.committed_deletions += counts.deletions;
+        self.committed_files += counts.files_changed();
+        self.committed_inserted += counts.inserted;
+        self.committed_edited += counts.edited;
+        let buckets = [(&mut self.extensions, &counts.by_extension), (&mut self.categories, &counts.by_category)];
+        for (buckets, counts) in buckets {
+            for (key, counts) in counts {
+                let bucket = buckets.entry(key.clone()).or_default();
+                bucket.committed_additions += counts.additions;
+                bucket.committed_deletions += counts.deletions;
+                bucket.committed_inserted += counts.inserted;
+                bucket.committed_edited += counts.edited;
+            }
+        }
+        for file in &counts.files {
+            if let Some(bucket) = self.extensions.get_mut(&path_extension(Path::new(&file.path))) {
+                bucket.committed_files += 1;
+            }
+            if let Some(bucket) = self.categories.get_mut(counts.category_of(&file.path)) {
+                bucket.committed_files += 1;
+            }
+        }
+    }
+
+    /// The stats themselves, followed by those of each file extension and then
+    /// each category in the breakdown, along with the extension or category.
+    fn with_breakdown(&self) -> impl Iterator<Item = (Option<&String>, Option<&String>, &RepoStats)> {
+        std::iter::once((None, None, self))
+            .chain(self.extensions.iter().map(|(extension, stats)| (Some(extension), None, stats)))
+            .chain(self.categories.iter().map(|(category, stats)| (None, Some(category), stats)))
+    }
+
+    /// Adds the counts of a commit to the committed totals and keeps them apart
+    /// for its own row. A flagged commit only gets its row.
+    fn add_commit(&mut self, commit: &git2::Commit, counts: DiffCounts, flagged: bool) {
+        let files = counts.files.clone();
+        let mut stats = RepoStats::default();
+        stats.add_committed(counts);
+        if !flagged {
+            *self += &stats;
+        }
+        self.commits.push(CommitStats {
+            sha: commit.id().to_string(),
+            author_email: commit.author().email().map(str::to_string),
+            timestamp: DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_default(),
+            stats,
+            files,
+            flagged,
+            is_merge: commit.parent_count() > 1,
+        });
+    }
+}
+
+/// Adds the staged and unstaged counts of each bucket, such as a file extension,
+/// to the pending counts of its stats in a breakdown.
+fn add_pending_buckets(
+    buckets: &mut BTreeMap<String, RepoStats>,
+    staged: BTreeMap<String, BucketCounts>,
+    unstaged: BTreeMap<String, BucketCounts>,
+) {
+    for (key, counts) in staged {
+        let bucket = buckets.entry(key).or_default();
+        bucket.staged_additions += counts.additions;
+        bucket.staged_deletions += counts.deletions;
+        bucket.pending_additions += counts.additions;
+        bucket.pending_deletions += counts.deletions;
+    }
+    for (key, counts) in unstaged {
+        let bucket = buckets.entry(key).or_default();
+        bucket.pending_additions += counts.additions;
+        bucket.pending_deletions += counts.deletions;
+    }
+}
+
+impl std::ops::AddAssign<&RepoStats> for RepoStats {
+    fn add_assign(&mut self, other: &RepoStats) {
+        self.committed_additions += other.committed_additions;
+        self.committed_deletions += other.committed_deletions;
+        self.pending_additions += other.pending_additions;
+        self.pending_deletions += other.pending_deletions;
+        self.staged_additions += other.staged_additions;
+        self.staged_deletions += other.staged_deletions;
+        self.committed_files += other.committed_files;
+        self.committed_inserted += other.committed_inserted;
+        self.committed_edited += other.committed_edited;
+        self.pending_files += other.pending_files;
+        for (extension, stats) in &other.extensions {
+            *self.extensions.entry(extension.clone()).or_default() += stats;
+        }
+        for (category, stats) in &other.categories {
+            *self.categories.entry(category.clone()).or_default() += stats;
+        }
+        self.commits.extend(other.commits.iter().cloned());
+    }
+}
+
+/// The in-memory state the watch loop carries from one poll to the next.
+#[derive(Debug, Default)]
+struct WatchState {
+    /// The latest stats per repository and author.
+    repo_stats: BTreeMap<(String, String), RepoStats>,
+    /// The name each tracked path is stored under, from `repository_names`.
+    repo_names: HashMap<PathBuf, String>,
+    /// The local day, branch and stats last written per repository and author.
+    last_stored: HashMap<(String, String), StoredSnapshot>,
+    /// The local days on which the tracked authors committed additions.
+    active_days: BTreeSet<NaiveDate>,
+    /// The milestones announced so far, when milestones are enabled.
+    milestones: Option<milestone::MilestoneTracker>,
+    /// Changes the database was too busy to take, written again on the next poll.
+    unsaved: Vec<LocChange>,
+    /// Paths whose repository has disappeared, skipped until it is back.
+    missing: HashSet<PathBuf>,
+    /// The counts of the commits already diffed on an earlier poll.
+    commit_cache: CommitCache,
+    /// Whether the authors of today's commits were reported for matching none of the tracked ones.
+    warned_unmatched: bool,
+    /// Paths reported for having no user in their git config while no author is given.
+    warned_no_author: HashSet<PathBuf>,
+    /// The commits reported for being flagged by `--flag-large-commit`.
+    flagged_commits: HashSet<String>,
+    /// The total over every repository before the latest poll, once there was one.
+    previous_total: Option<RepoStats>,
+    /// The id every row written by this run is tagged with.
+    session_id: Option<String>,
+    /// The hour days start at, from `--day-start-hour`.
+    day_start_hour: u32,
+    /// The committed totals of the latest polls, for the pace shown.
+    velocity: velocity::Velocity,
+    /// Whether polling is slowed down for lack of changes, when `--idle-minutes` is set.
+    idle: Option<idle::IdleTracker>,
+}
+
+/// What was last written for a repository and author.
+type StoredSnapshot = (NaiveDate, Option<String>, RepoStats);
+
+impl WatchState {
+    /// Records the snapshot about to be stored for a repository and author,
+    /// returning false if it repeats the last stored one.
+    fn record_stored(&mut self, repo_name: &str, author: &str, snapshot: StoredSnapshot) -> bool {
+        let key = (repo_name.to_string(), author.to_string());
+        if self.last_stored.get(&key) == Some(&snapshot) {
+            return false;
+        }
+        self.last_stored.insert(key, snapshot);
+        true
+    }
+
+    /// Stores a poll's changes in one transaction, keeping them for the next
+    /// poll if the database stays busy.
+    ///
+    /// Once changes are kept all later ones are too, so they reach the database in order.
+    async fn save_changes(&mut self, store: &dyn Store, mut changes: Vec<LocChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        for change in &mut changes {
+            change.session_id.clone_from(&self.session_id);
+        }
+        if !self.unsaved.is_empty() {
+            self.unsaved.extend(changes);
+            return;
+        }
+
+        match store::store_changes_with_retry(store, &changes).await {
+            Ok(()) => {
+                for change in &changes {
+                    debug!(
+                        repo = %change.repo_name,
+                        author = change.author.as_deref(),
+                        committed = change.is_committed,
+                        extension = change.extension.as_deref(),
+                        category = change.category.as_deref(),
+                        additions = change.additions,
+                        deletions = change.deletions,
+                        "stored change"
+                    );
+                }
+            }
+            Err(e) if store::is_busy(&e) => {
+                warn!(changes = changes.len(), error = %e, "database busy, keeping changes for the next poll");
+                self.unsaved.extend(changes);
+            }
+            Err(e) => error!(changes = changes.len(), error = %e, "error storing changes"),
+        }
+    }
+
+    /// Writes the changes kept by earlier polls, keeping them again if the
+    /// database is still busy.
+    async fn flush_unsaved(&mut self, store: &dyn Store) {
+        let unsaved = std::mem::take(&mut self.unsaved);
+        self.save_changes(store, unsaved).await;
+    }
+
+    /// Writes the changes kept by earlier polls one last time before exiting.
+    ///
+    /// # Returns
+    ///
+    /// An error if the database stayed busy, as the changes kept are lost once the watcher exits.
+    async fn flush_before_exit(&mut self, store: &dyn Store) -> Result<(), String> {
+        self.flush_unsaved(store).await;
+        match self.unsaved.is_empty() {
+            true => Ok(()),
+            false => Err(format!("database is busy; {} change(s) were not stored", self.unsaved.len())),
+        }
+    }
+
+    /// Returns the milestone the day's committed LoC just crossed, if any, along
+    /// with the number of repositories that contributed to it.
+    fn crossed_milestone(&mut self, deletions_as: DeletionsAs) -> Option<(i32, usize)> {
+        let repo_totals = self.repo_totals();
+        let today = self.today();
+        let tracker = self.milestones.as_mut()?;
+        let committed: Vec<i32> = repo_totals
+            .values()
+            .map(|stats| deletions_as.committed(stats))
+            .filter(|loc| *loc > 0)
+            .collect();
+        let reached = tracker.crossed(today, committed.iter().sum())?;
+        Some((reached, committed.len()))
+    }
+
+    /// The latest stats per repository, summed over authors.
+    fn repo_totals(&self) -> BTreeMap<String, RepoStats> {
+        status::by_repo(&self.repo_stats)
+    }
+
+    /// The current streak of consecutive days with committed additions.
+    fn streak(&self) -> u32 {
+        streak::current_streak(&self.active_days, self.today())
+    }
+
+    /// The local day it is now, which starts at `day_start_hour`.
+    fn today(&self) -> NaiveDate {
+        day_of(&Local::now(), self.day_start_hour)
+    }
+}
+
+/// Line counts of the files of a diff in one bucket of the breakdown.
+#[derive(Debug, Clone, Copy, Default)]
+struct BucketCounts {
+    additions: i32,
+    deletions: i32,
+    /// The additions of hunks without deletions, when counted by hunk.
+    inserted: i32,
+    /// The additions of hunks with deletions, when counted by hunk.
+    edited: i32,
+}
+
+impl BucketCounts {
+    /// Adds a file, along with the split by hunk of its additions.
+    fn add(&mut self, file: &FileChange, (inserted, edited): (i32, i32)) {
+        self.additions += file.additions;
+        self.deletions += file.deletions;
+        self.inserted += inserted;
+        self.edited += edited;
+    }
+}
+
+/// Line counts of a single diff.
+#[derive(Debug, Clone, Default)]
+struct DiffCounts {
+    additions: i32,
+    deletions: i32,
+    /// The counts of each file the diff touches, for the first `MAX_FILE_COUNTS` of them.
+    files: Vec<FileChange>,
+    /// The files touched past `MAX_FILE_COUNTS`, only counted in the totals.
+    more_files: i32,
+    /// Additions and deletions per file extension, when requested.
+    by_extension: BTreeMap<String, BucketCounts>,
+    /// Additions and deletions of code and of docs, when requested.
+    by_category: BTreeMap<String, BucketCounts>,
+    /// The files of `files` counted as docs, when split by category.
+    doc_files: BTreeSet<String>,
+    /// The additions of hunks without deletions, when counted by hunk.
+    inserted: i32,
+    /// The additions of hunks with deletions, when counted by hunk.
+    edited: i32,
+    /// The split by hunk of the file being counted, added to its buckets with the file.
+    file_hunks: (i32, i32),
+}
+
+/// How many files of a single diff get counts of their own. A vendored
+/// dependency can touch tens of thousands of files, and every commit's counts
+/// are kept in memory for the day and stored row by row.
+const MAX_FILE_COUNTS: usize = 1_000;
+
+impl DiffCounts {
+    /// Adds a file, whose lines and hunks have all been counted, to the totals.
+    fn add_file(&mut self, file: FileChange, options: &CollectOptions) {
+        self.additions += file.additions;
+        self.deletions += file.deletions;
+        let hunks = std::mem::take(&mut self.file_hunks);
+        if options.by_language && file.additions + file.deletions > 0 {
+            self.by_extension.entry(path_extension(Path::new(&file.path))).or_default().add(&file, hunks);
+        }
+        if options.by_category {
+            let category = options.category_of(Path::new(&file.path));
+            if file.additions + file.deletions > 0 {
+                self.by_category.entry(category.to_string()).or_default().add(&file, hunks);
+            }
+            if category == DOCS_CATEGORY && self.files.len() < MAX_FILE_COUNTS {
+                self.doc_files.insert(file.path.clone());
+            }
+        }
+        if self.files.len() < MAX_FILE_COUNTS {
+            self.files.push(file);
+        } else {
+            self.more_files += 1;
+        }
+    }
+
+    /// The category of a file the diff kept counts of, as told apart by `add_file`.
+    fn category_of(&self, path: &str) -> &'static str {
+        match self.doc_files.contains(path) {
+            true => DOCS_CATEGORY,
+            false => CODE_CATEGORY,
+        }
+    }
+
+    /// The number of files the diff touches.
+    fn files_changed(&self) -> i32 {
+        self.files.len() as i32 + self.more_files
+    }
+
+    /// Adds the lines of a hunk, whose lines have all been counted, to the split by hunk
+    /// of the totals and of the file it belongs to.
+    fn add_hunk(&mut self, (additions, deletions): (i32, i32)) {
+        match deletions {
+            0 => {
+                self.inserted += additions;
+                self.file_hunks.0 += additions;
+            }
+            _ => {
+                self.edited += additions;
+                self.file_hunks.1 += additions;
+            }
+        }
+    }
+}
+
+/// The counts of commits already diffed, by commit SHA.
+///
+/// Commits never change, so entries are kept for as long as the cache lives. The
+/// counts depend on the CollectOptions, so a cache is only to be reused with the
+/// same options.
+#[derive(Debug, Default)]
+pub struct CommitCache {
+    counts: HashMap<Oid, Option<DiffCounts>>,
+    /// How many commits had to be diffed because they were not cached yet.
+    diffed: usize,
+}
+
+impl CommitCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        CommitCache::default()
+    }
+
+    /// The number of commits that were diffed rather than found in the cache.
+    pub fn diffed(&self) -> usize {
+        self.diffed
+    }
+
+    /// Counts a commit, diffing it only the first time it is seen.
+    fn count(
+        &mut self,
+        repo: &Repository,
+        commit: &git2::Commit,
+        options: &CollectOptions,
+    ) -> Result<Option<DiffCounts>, git2::Error> {
+        if let Some(counts) = self.counts.get(&commit.id()) {
+            return Ok(counts.clone());
+        }
+        let counts = count_commit(repo, commit, options)?;
+        self.diffed += 1;
+        self.counts.insert(commit.id(), counts.clone());
+        Ok(counts)
+    }
+}
+
+/// The set of author identities whose commits are counted.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorFilter {
+    names: Vec<String>,
+    emails: Vec<String>,
+    /// The aliases commit identities are normalized through before matching.
+    aliases: author_map::AuthorMap,
+}
+
+impl AuthorFilter {
+    /// Normalizes the configured identities and every commit author through an
+    /// author map, so all aliases of a person are counted under one name.
+    pub fn new(names: Vec<String>, emails: Vec<String>, aliases: author_map::AuthorMap) -> Self {
+        let mut canonical_names: Vec<String> = Vec::new();
+        let mut plain_emails = Vec::new();
+        for name in &names {
+            canonical_names.push(aliases.canonical(name).to_string());
+        }
+        // An email listed in the map is tracked under its canonical name instead
+        for email in emails {
+            match aliases.canonical(&email) {
+                canonical if canonical != email => canonical_names.push(canonical.to_string()),
+                _ => plain_emails.push(email),
+            }
+        }
+        let mut seen = BTreeSet::new();
+        canonical_names.retain(|name| seen.insert(name.clone()));
+
+        AuthorFilter { names: canonical_names, emails: plain_emails, aliases }
+    }
+
+    /// Returns every configured identity, names first.
+    fn identities(&self) -> impl Iterator<Item = &String> {
+        self.names.iter().chain(&self.emails)
+    }
+
+    /// Returns whether no identity is configured.
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.emails.is_empty()
+    }
+
+    /// Returns the filter a repository's commits are counted with.
+    ///
+    /// # Arguments
+    ///
+    /// * `repo` - A reference to the Repository object.
+    ///
+    /// # Returns
+    ///
+    /// This filter if it has any identity, otherwise one for the `user.name` and
+    /// `user.email` of the repository's git config, or None if neither is set.
+    fn for_repository(&self, repo: &Repository) -> Option<Cow<'_, AuthorFilter>> {
+        if !self.is_empty() {
+            return Some(Cow::Borrowed(self));
+        }
+        let config = repo.config().ok()?;
+        let setting = |key: &str| config.get_string(key).ok().filter(|value| !value.trim().is_empty());
+        let filter = AuthorFilter::new(
+            setting("user.name").into_iter().collect(),
+            setting("user.email").into_iter().collect(),
+            self.aliases.clone(),
+        );
+        (!filter.is_empty()).then_some(Cow::Owned(filter))
+    }
+
+    /// Returns the identity that uncommitted changes are attributed to.
+    fn owner(&self) -> Option<&String> {
+        self.identities().next()
+    }
+
+    /// Returns the configured identity matching a commit signature, preferring
+    /// the name and falling back to a case-insensitive email match.
+    fn matching(&self, signature: &git2::Signature) -> Option<&String> {
+        let name = signature.name().unwrap_or_default();
+        let email = signature.email().unwrap_or_default();
+
+        if let Some(canonical) = self.aliases.resolve(name, email) {
+            if let Some(author) = self.names.iter().find(|candidate| candidate.as_str() == canonical) {
+                return Some(author);
+            }
+        }
+
+        self.names
+            .iter()
+            .find(|candidate| candidate.as_str() == name)
+            .or_else(|| {
+                self.emails
+                    .iter()
+                    .find(|candidate| !email.is_empty() && candidate.eq_ignore_ascii_case(email))
+            })
+    }
+}
+
+/// The extensions of the files counted as docs by `--by-category`, unless `--doc-extension` is given.
+pub const DEFAULT_DOC_EXTENSIONS: &[&str] = &["md", "rst", "txt", "adoc"];
+
+/// The author the pending changes of a repository are counted under when no
+/// `--author` is given and its git config has no user either.
+pub const UNKNOWN_AUTHOR: &str = "unknown";
+
+/// The category of `--by-category` that files not counted as docs fall in.
+pub const CODE_CATEGORY: &str = "code";
+
+/// The category of `--by-category` for the files with one of the doc extensions.
+pub const DOCS_CATEGORY: &str = "docs";
+
+/// Options controlling which changes are counted when collecting stats.
+#[derive(Debug, Clone, Default)]
+pub struct CollectOptions {
+    /// Count merge commits by diffing against their first parent instead of skipping them.
+    pub count_merges: bool,
+    /// Break the counts down by file extension.
+    pub by_language: bool,
+    /// Split the counts into code and docs, see `doc_extensions`.
+    pub by_category: bool,
+    /// The lowercased extensions of the files counted as docs, or `DEFAULT_DOC_EXTENSIONS` when empty.
+    pub doc_extensions: Vec<String>,
+    /// Tell the additions of hunks that only add lines from those of hunks that also delete some.
+    pub by_hunk: bool,
+    /// Globs of paths whose changes are not counted.
+    pub exclude: Vec<String>,
+    /// Directories the counts are limited to, relative to the repository root.
+    pub subpaths: Vec<String>,
+    /// Commits whose summary matches this pattern are not counted.
+    pub skip_message: Option<Regex>,
+    /// The offset commits are dated in, instead of the one each was made in.
+    pub timezone: Option<FixedOffset>,
+    /// The hour days start at; commits made earlier count for the day before.
+    pub day_start_hour: u32,
+    /// Count the lines of binary files as if they were text.
+    pub count_binary: bool,
+    /// The branch whose commits are counted, instead of the checked out one.
+    pub branch: Option<String>,
+    /// The remote-tracking branch whose commits are counted, e.g. `origin/main`.
+    pub remote_branch: Option<String>,
+    /// Leave lines out whose only change is whitespace.
+    pub ignore_whitespace: bool,
+    /// Leave blank lines and single-line comments out, see `is_significant_line`.
+    pub significant_lines_only: bool,
+    /// Count the pointer change of a submodule whose checked out commit moved as pending work.
+    pub count_submodules: bool,
+    /// The most commits visited when looking for today's, newest first.
+    pub max_commits: Option<usize>,
+    /// Count a commit without a parent, such as the first one, as adding its whole tree.
+    pub count_root_commits: bool,
+    /// Flag commits adding more than this many net LoC instead of counting them.
+    pub flag_large_commit: Option<u32>,
+}
+
+impl CollectOptions {
+    /// The category of `by_category` a file falls in, by its extension.
+    fn category_of(&self, path: &Path) -> &'static str {
+        let extension = path_extension(path);
+        let is_doc = match self.doc_extensions.is_empty() {
+            true => DEFAULT_DOC_EXTENSIONS.contains(&extension.as_str()),
+            false => self.doc_extensions.contains(&extension),
+        };
+        match is_doc {
+            true => DOCS_CATEGORY,
+            false => CODE_CATEGORY,
+        }
+    }
+
+    /// Whether a commit with these counts is too large to count, per `flag_large_commit`.
+    fn flags_commit(&self, counts: &DiffCounts) -> bool {
+        self.flag_large_commit.is_some_and(|limit| i64::from(counts.additions - counts.deletions) > i64::from(limit))
+    }
+
+    /// Whether a commit is left out of the committed counts.
+    fn skips_commit(&self, commit: &git2::Commit) -> bool {
+        // Merges mostly repeat work from the merged branch, so they are opt-in
+        if commit.parent_count() > 1 && !self.count_merges {
+            return true;
+        }
+
+        match (&self.skip_message, commit.summary()) {
+            (Some(pattern), Some(summary)) => pattern.is_match(summary),
+            _ => false,
+        }
+    }
+
+    /// Creates the options of a diff to count, restricted to the paths that
+    /// should be counted.
+    fn diff_options(&self) -> DiffOptions {
+        let mut diff_opts = DiffOptions::new();
+        // Binary files have no lines, so they are only counted when read as text
+        diff_opts.force_text(self.count_binary);
+        diff_opts.ignore_whitespace(self.ignore_whitespace);
+        self.apply_pathspec(&mut diff_opts);
+        diff_opts
+    }
+
+    fn apply_pathspec(&self, diff_opts: &mut DiffOptions) {
+        if self.exclude.is_empty() && self.subpaths.is_empty() {
+            return;
+        }
+        // libgit2 lets the exclusions win only when they precede the paths to include
+        for pattern in &self.exclude {
+            diff_opts.pathspec(format!("!{}", pattern));
+        }
+        if self.subpaths.is_empty() {
+            diff_opts.pathspec("*");
+        }
+        for subpath in &self.subpaths {
+            diff_opts.pathspec(subpath);
+        }
+    }
+}
+
+/// Splits a repository's stats into committed and pending change records.
+/// 
+/// # Arguments
+/// 
+/// * `repo_name` - The name of the repository the stats belong to.
+/// * `author` - The author the stats were collected for.
+/// * `branch` - The branch that was checked out when the stats were collected.
+/// * `stats` - A reference to the collected RepoStats.
+/// * `timestamp` - The time the stats were collected.
+/// 
+/// # Returns
+/// 
+/// The pending totals of the repository and of each file extension and category
+/// in the breakdown, followed by a committed row for each commit, dated at the
+/// commit time, and for each file extension and category of that commit.
+pub fn changes_from_stats(
+    repo_name: &str,
+    author: &str,
+    branch: Option<&str>,
+    stats: &RepoStats,
+    timestamp: DateTime<Utc>,
+) -> Vec<LocChange> {
+    let pending_email = &stats.pending_email;
+    let change = |timestamp,
+                  (extension, category): (Option<&String>, Option<&String>),
+                  stats: &RepoStats,
+                  commit: Option<&CommitStats>| {
+        let is_committed = commit.is_some();
+        let (additions, deletions, staged_additions, staged_deletions, files_changed) = match is_committed {
+            true => (stats.committed_additions, stats.committed_deletions, 0, 0, stats.committed_files),
+            false => (
+                stats.pending_additions,
+                stats.pending_deletions,
+                stats.staged_additions,
+                stats.staged_deletions,
+                stats.pending_files,
+            ),
+        };
+        LocChange {
+            repo_name: repo_name.to_string(),
+            timestamp,
+            author: Some(author.to_string()),
+            author_email: match commit {
+                Some(commit) => commit.author_email.clone(),
+                None => pending_email.clone(),
+            },
+            additions,
+            deletions,
+            is_committed,
+            extension: extension.cloned(),
+            category: category.cloned(),
+            branch: branch.map(str::to_string),
+            commit_sha: commit.map(|commit| commit.sha.clone()),
+            staged_additions,
+            staged_deletions,
+            files_changed,
+            inserted_lines: if is_committed { stats.committed_inserted } else { 0 },
+            edited_lines: if is_committed { stats.committed_edited } else { 0 },
+            flagged: commit.is_some_and(|commit| commit.flagged),
+            session_id: None,
+            is_merge: commit.is_some_and(|commit| commit.is_merge),
+            files: match (commit, extension, category) {
+                (Some(commit), None, None) => commit.files.clone(),
+                _ => Vec::new(),
+            },
+        }
+    };
+
+    let mut changes: Vec<LocChange> = stats
+        .with_breakdown()
+        .map(|(extension, category, stats)| change(timestamp, (extension, category), stats, None))
+        .collect();
+    for commit in &stats.commits {
+        for (extension, category, stats) in commit.stats.with_breakdown() {
+            changes.push(change(commit.timestamp, (extension, category), stats, Some(commit)));
+        }
+    }
+    changes
+}
+
+/// Restrictions applied when reading stored changes back.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFilter {
+    /// Only include changes recorded for this author.
+    pub author: Option<String>,
+    /// Only include changes of this repository.
+    pub repo_name: Option<String>,
+    /// Inclusive lower bound on the change timestamp.
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the change timestamp.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ChangeFilter {
+    /// Creates a filter covering whole local days, both bounds inclusive.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `since` - The optional first day to include.
+    /// * `until` - The optional last day to include.
+    /// 
+    /// # Returns
+    /// 
+    /// A Result containing the filter, or an error if `since` is after `until`.
+    fn for_days(since: Option<NaiveDate>, until: Option<NaiveDate>) -> Result<Self, String> {
+        ChangeFilter::for_range(since.map(report::TimeBound::Day), until.map(report::TimeBound::Day))
+    }
+
+    /// Creates a filter from `--since` and `--until` bounds, a day bound covering
+    /// the whole local day.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `since` - The optional first day or instant to include.
+    /// * `until` - The optional last day to include, or instant to stop before.
+    /// 
+    /// # Returns
+    /// 
+    /// A Result containing the filter, or an error if `since` is after `until`.
+    fn for_range(since: Option<report::TimeBound>, until: Option<report::TimeBound>) -> Result<Self, String> {
+        ChangeFilter::for_range_from(since, until, 0)
+    }
+
+    /// Creates a filter from `--since` and `--until` bounds like `for_range`, with
+    /// days starting at `day_start_hour`.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - The optional first day or instant to include.
+    /// * `until` - The optional last day to include, or instant to stop before.
+    /// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the filter, or an error if `since` is after `until`.
+    fn for_range_from(
+        since: Option<report::TimeBound>,
+        until: Option<report::TimeBound>,
+        day_start_hour: u32,
+    ) -> Result<Self, String> {
+        if let (Some(since), Some(until)) = (since, until) {
+            if since.start(day_start_hour) > until.end(day_start_hour) {
+                return Err(format!("--since {} is after --until {}", since, until));
+            }
+        }
+
+        Ok(ChangeFilter {
+            since: since.map(|since| since.start(day_start_hour)),
+            until: until.map(|until| until.end(day_start_hour)),
+            ..Default::default()
+        })
+    }
+}
+
+/// Returns the name of the checked out branch, or the short SHA of the HEAD
+/// commit when it is detached.
+/// 
+/// # Arguments
+/// 
+/// * `repo` - A reference to the Repository object.
+fn current_branch(repo: &Repository) -> Option<String> {
+    match repo.head() {
+        Ok(head) if repo.head_detached().unwrap_or(false) => {
+            let commit = head.peel_to_commit().ok()?;
+            let short_id = commit.as_object().short_id().ok()?;
+            short_id.as_str().map(str::to_string)
+        }
+        Ok(head) => head.shorthand().map(str::to_string),
+        // The branch of a repository without commits is only known symbolically
+        Err(_) => repo
+            .find_reference("HEAD")
+            .ok()?
+            .symbolic_target()
+            .map(|target| target.trim_start_matches("refs/heads/").to_string()),
+    }
+}
+
+/// Returns the day a commit was made on, as seen by its author.
+/// 
+/// # Arguments
+/// 
+/// * `commit_time` - A reference to the commit time.
+/// * `timezone` - The offset to date the commit in, overriding the commit's own offset.
+/// * `day_start_hour` - The hour days start at.
+/// 
+/// # Returns
+/// 
+/// The date of the commit, or None if its timestamp or offset is out of range.
+fn commit_date(commit_time: &Time, timezone: Option<FixedOffset>, day_start_hour: u32) -> Option<NaiveDate> {
+    let offset = match timezone {
+        Some(timezone) => timezone,
+        None => FixedOffset::east_opt(commit_time.offset_minutes() * 60)?,
+    };
+    DateTime::from_timestamp(commit_time.seconds(), 0).map(|dt| day_of(&dt.with_timezone(&offset), day_start_hour))
+}
+
+/// Returns the day an instant falls on when days start at `day_start_hour`
+/// rather than midnight.
+/// 
+/// # Arguments
+/// 
+/// * `instant` - The instant, in the timezone it is dated in.
+/// * `day_start_hour` - The hour days start at.
+/// 
+/// # Returns
+/// 
+/// The date of the instant, the one before its calendar date when it is earlier than the start hour.
+fn day_of<Tz: TimeZone>(instant: &DateTime<Tz>, day_start_hour: u32) -> NaiveDate {
+    (instant.naive_local() - chrono::Duration::hours(day_start_hour.into())).date()
+}
+
+/// Checks if a commit was made today.
+/// 
+/// # Arguments
+/// 
+/// * `commit_time` - A reference to the commit time.
+/// * `timezone` - The offset both the commit and today are dated in, or None to
+///   date the commit in its own offset and today in local time.
+/// * `day_start_hour` - The hour days start at.
+/// 
+/// # Returns
+/// 
+/// Returns true if the commit was made today, otherwise false.
+fn is_commit_from_today(commit_time: &Time, timezone: Option<FixedOffset>, day_start_hour: u32) -> bool {
+    let today = match timezone {
+        Some(timezone) => day_of(&Utc::now().with_timezone(&timezone), day_start_hour),
+        None => day_of(&Local::now(), day_start_hour),
+    };
+    commit_date(commit_time, timezone, day_start_hour) == Some(today)
+}
+
+/// Returns the path of a diffed file, its old one if it was deleted.
+fn delta_path<'a>(delta: &DiffDelta<'a>) -> Option<&'a Path> {
+    delta.new_file().path().or_else(|| delta.old_file().path())
+}
+
+/// Returns the file a delta touches, along with its old path when it was
+/// renamed, with no lines counted yet.
+fn delta_file(delta: &DiffDelta) -> Option<FileChange> {
+    let path = delta_path(delta)?.to_string_lossy().into_owned();
+    let old_path = match delta.status() {
+        Delta::Renamed => delta.old_file().path().map(|path| path.to_string_lossy().into_owned()),
+        _ => None,
+    };
+    Some(FileChange { path, old_path, ..Default::default() })
+}
+
+/// Returns the lowercased extension of a path, or an empty string when it has none.
+fn path_extension(path: &Path) -> String {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Whether a line holds code rather than only whitespace or a comment.
+///
+/// This is a heuristic that looks at the line alone: a line whose content starts
+/// with `//`, `#` or `--`, the single-line comments of most common languages, is
+/// taken for a comment. Rust attributes such as `#[test]` are code. Comments after
+/// code, block comments and the lines inside strings are not told apart.
+fn is_significant_line(content: &[u8]) -> bool {
+    let line = String::from_utf8_lossy(content);
+    let line = line.trim();
+    if line.is_empty() || line.starts_with("//") || line.starts_with("--") {
+        return false;
+    }
+    !line.starts_with('#') || line.starts_with("#[") || line.starts_with("#![")
+}
+
+/// Counts the additions and deletions of a diff.
+/// 
+/// The diff is streamed one file at a time and its lines are only counted, so
+/// a huge diff is never held in memory as a whole.
+/// 
+/// # Arguments
+/// 
+/// * `diff` - A reference to the Diff to count.
+/// * `options` - A reference to the CollectOptions, for the breakdown by extension
+///   and category and which lines count.
+/// 
+/// # Returns
+/// 
+/// A Result containing the DiffCounts if successful, or a git2::Error if an error occurs.
+fn count_diff(diff: &Diff, options: &CollectOptions) -> std::result::Result<DiffCounts, git2::Error> {
+    // The callbacks add to the file and hunk being streamed, each finished once the next one starts
+    let tally = RefCell::new((DiffCounts::default(), None::<FileChange>, None::<(i32, i32)>));
+
+    let mut hunk_cb = |_: DiffDelta, _: DiffHunk| {
+        let (counts, _, hunk) = &mut *tally.borrow_mut();
+        if let Some(lines) = hunk.replace((0, 0)) {
+            counts.add_hunk(lines);
+        }
+        true
+    };
+    diff.foreach(
+        &mut |delta, _| {
+            let (counts, current, hunk) = &mut *tally.borrow_mut();
+            // The last hunk first, as it belongs to the file it finishes
+            if let Some(lines) = hunk.take() {
+                counts.add_hunk(lines);
+            }
+            if let Some(file) = current.take() {
+                counts.add_file(file, options);
+            }
+            // Every delta is a changed file, even one without lines such as a pure rename
+            *current = delta_file(&delta);
+            true
+        },
+        None,
+        // Hunks are only told apart when asked to, as most diffs need just the line counts
+        match options.by_hunk {
+            true => Some(&mut hunk_cb),
+            false => None,
+        },
+        Some(&mut |_, _, line| {
+            if options.significant_lines_only && !is_significant_line(line.content()) {
+                return true;
+            }
+            if let (_, Some(file), hunk) = &mut *tally.borrow_mut() {
+                match line.origin() {
+                    '+' => file.additions += 1,
+                    '-' => file.deletions += 1,
+                    _ => {}
+                }
+                // Hunks are only streamed, and so tallied, with `by_hunk`
+                if let Some((additions, deletions)) = hunk {
+                    match line.origin() {
+                        '+' => *additions += 1,
+                        '-' => *deletions += 1,
+                        _ => {}
+                    }
+                }
+            }
+            true
+        }),
+    )?;
+
+    let (mut counts, current, hunk) = tally.into_inner();
+    if let Some(lines) = hunk {
+        counts.add_hunk(lines);
+    }
+    if let Some(file) = current {
+        counts.add_file(file, options);
+    }
+    Ok(counts)
+}
+
+/// Counts the number of additions and deletions in the working directory of a repository.
+/// 
+/// Staged edits are measured from HEAD to the index, and unstaged ones from the
+/// index to the working directory; a repository without commits is measured
+/// against an empty tree. Untracked files count as unstaged additions of their
+/// full content, while files excluded by `.gitignore` are left out. Submodules
+/// are left out too, unless `count_submodules` is set, as a moved submodule
+/// only shows up as the change of the commit it points at.
+/// 
+/// # Arguments
+/// 
+/// * `repo` - A reference to the Repository object.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// 
+/// # Returns
+/// 
+/// The staged and the unstaged DiffCounts, each empty if it could not be diffed.
+fn count_file_changes(repo: &Repository, options: &CollectOptions) -> (DiffCounts, DiffCounts) {
+    let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+    let mut diff_opts = options.diff_options();
+    diff_opts.ignore_submodules(!options.count_submodules);
+    let staged = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_opts))
+        .and_then(|diff| count_diff(&diff, options))
+        .unwrap_or_default();
+
+    let mut diff_opts = options.diff_options();
+    diff_opts
+        .ignore_submodules(!options.count_submodules)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let unstaged = repo
+        .diff_index_to_workdir(None, Some(&mut diff_opts))
+        .and_then(|diff| count_diff(&diff, options))
+        .unwrap_or_default();
+
+    (staged, unstaged)
+}
+
+/// Retrieves the changes for a repository made by a set of authors.
+/// 
+/// Every configured identity gets an entry. Uncommitted changes have no author,
+/// so they are attributed to the filter's owner.
+/// 
+/// # Arguments
+/// 
+/// * `repo` - A reference to the Repository object.
+/// * `authors` - A reference to the AuthorFilter describing whom to track.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// 
+/// # Returns
+/// 
+/// A Result containing RepoStats per author, with each of today's commits listed
+/// in its `commits`, or a git2::Error if an error occurs.
+pub fn collect_stats(
+    repo: &Repository,
+    authors: &AuthorFilter,
+    options: &CollectOptions,
+) -> std::result::Result<BTreeMap<String, RepoStats>, git2::Error> {
+    collect_stats_cached(repo, authors, options, &mut CommitCache::new())
+}
+
+/// Retrieves the changes for a repository like `collect_stats`, reusing the
+/// counts of commits diffed by earlier calls.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `authors` - A reference to the AuthorFilter describing whom to track.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// * `cache` - The CommitCache of earlier calls with the same options.
+///
+/// # Returns
+///
+/// A Result containing RepoStats per author, or a git2::Error if an error occurs.
+pub fn collect_stats_cached(
+    repo: &Repository,
+    authors: &AuthorFilter,
+    options: &CollectOptions,
+    cache: &mut CommitCache,
+) -> std::result::Result<BTreeMap<String, RepoStats>, git2::Error> {
+    let mut author_stats: BTreeMap<String, RepoStats> = authors
+        .identities()
+        .map(|author| (author.clone(), RepoStats::default()))
+        .collect();
+
+    // The workdir diff already covers every changed file, so it is computed once
+    if let Some(stats) = authors.owner().and_then(|owner| author_stats.get_mut(owner)) {
+        add_pending_stats(repo, stats, authors.emails.first(), options)?;
+    }
+
+    // Get all commits from today
+    let revwalk = match walk_history(repo, options)? {
+        Some(revwalk) => revwalk,
+        None => return Ok(author_stats),
+    };
+    
+    // Bounds the cost of a poll on repositories with a long history
+    for oid in revwalk.take(options.max_commits.unwrap_or(usize::MAX)) {
+        let oid = oid?;
+        let commit = repo.find_commit(oid)?;
+        
+        // Skip if not from today; older ancestors can be interleaved with
+        // today's commits once merges are involved, so keep walking
+        if !is_commit_from_today(&commit.time(), options.timezone, options.day_start_hour) {
+            continue;
+        }
+
+        if options.skips_commit(&commit) {
+            continue;
+        }
+        
+        // Check author
+        let matched = authors.matching(&commit.author());
+        
+        if let Some(stats) = matched.and_then(|author| author_stats.get_mut(author)) {
+            if let Some(counts) = cache.count(repo, &commit, options)? {
+                let flagged = options.flags_commit(&counts);
+                stats.add_commit(&commit, counts, flagged);
+            }
+        }
+    }
+
+    Ok(author_stats)
+}
+
+/// Retrieves the uncommitted changes of a repository alone, attributed to
+/// `UNKNOWN_AUTHOR`, for a repository whose commits have no one to be matched against.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+///
+/// # Returns
+///
+/// A Result containing the RepoStats of `UNKNOWN_AUTHOR`, without committed counts,
+/// or a git2::Error if an error occurs.
+fn collect_pending_stats(
+    repo: &Repository,
+    options: &CollectOptions,
+) -> std::result::Result<BTreeMap<String, RepoStats>, git2::Error> {
+    let mut stats = RepoStats::default();
+    add_pending_stats(repo, &mut stats, None, options)?;
+    Ok(BTreeMap::from([(UNKNOWN_AUTHOR.to_string(), stats)]))
+}
+
+/// Adds the uncommitted changes of a repository to the stats of the person they are attributed to.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `stats` - The RepoStats the changes are added to.
+/// * `email` - The email of the person, `user.email` of the git config if not given.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+fn add_pending_stats(
+    repo: &Repository,
+    stats: &mut RepoStats,
+    email: Option<&String>,
+    options: &CollectOptions,
+) -> std::result::Result<(), git2::Error> {
+    let mut status_opts = StatusOptions::new();
+    status_opts.include_untracked(true);
+    let statuses = repo.statuses(Some(&mut status_opts))?;
+    if statuses.iter().all(|status| status.status() == Status::CURRENT) {
+        return Ok(());
+    }
+
+    let (staged, unstaged) = count_file_changes(repo, options);
+    stats.add_pending(staged, unstaged);
+    let configured = || repo.config().and_then(|config| config.get_string("user.email")).ok();
+    stats.pending_email = email.cloned().or_else(|| configured().filter(|email| !email.trim().is_empty()));
+    Ok(())
+}
+
+/// Starts a walk over the history of the tracked branch, newest commit first.
+///
+/// The tracked branch is `--branch` if it exists and the checked out one otherwise.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `options` - A reference to the CollectOptions naming the branch.
+///
+/// # Returns
+///
+/// A Result containing the walk, or None if the branch has no commits yet.
+fn walk_history<'r>(repo: &'r Repository, options: &CollectOptions) -> Result<Option<Revwalk<'r>>, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+
+    if let Some(remote_branch) = &options.remote_branch {
+        match repo.find_reference(&format!("refs/remotes/{}", remote_branch)) {
+            Ok(reference) => {
+                revwalk.push(reference.peel_to_commit()?.id())?;
+                return Ok(Some(revwalk));
+            }
+            Err(e) if e.code() == ErrorCode::NotFound => warn!(
+                path = %repo.path().display(),
+                remote_branch = %remote_branch,
+                "remote-tracking branch not found, counting the commits of HEAD instead"
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(branch) = &options.branch {
+        match repo.find_reference(&format!("refs/heads/{}", branch)) {
+            Ok(reference) => {
+                revwalk.push(reference.peel_to_commit()?.id())?;
+                return Ok(Some(revwalk));
+            }
+            Err(e) if e.code() == ErrorCode::NotFound => warn!(
+                path = %repo.path().display(),
+                branch = %branch,
+                "branch not found, counting the commits of HEAD instead"
+            ),
+            Err(e) => return Err(e),
+        }
+    }
+
+    // A freshly initialised repository has no commits to walk yet
+    match repo.head() {
+        Ok(_) => {}
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    revwalk.push_head()?;
+    Ok(Some(revwalk))
+}
+
+/// Counts the changes a commit made relative to its first parent.
+/// 
+/// # Arguments
+/// 
+/// * `repo` - A reference to the Repository object.
+/// * `commit` - A reference to the commit to count.
+/// * `options` - A reference to the CollectOptions controlling what is counted.
+/// 
+/// # Returns
+/// 
+/// A Result containing the DiffCounts, or None for a root commit which has no
+/// parent to diff against unless `count_root_commits` is set.
+fn count_commit(
+    repo: &Repository,
+    commit: &git2::Commit,
+    options: &CollectOptions,
+) -> std::result::Result<Option<DiffCounts>, git2::Error> {
+    // A root commit is measured against the empty tree when it is to be counted
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) if options.count_root_commits => None,
+        Err(_) => return Ok(None),
+    };
+
+    let commit_tree = commit.tree()?;
+    let mut diff_opts = options.diff_options();
+    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit_tree), Some(&mut diff_opts))?;
+    // Count a renamed file by its edits rather than a full delete and re-add
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    count_diff(&diff, options).map(Some)
+}
+
+/// Recomputes the stats of every repository, stores them and updates the
+/// in-memory summary.
+/// 
+/// Stats identical to the ones last stored for the same repository, author and
+/// day are not stored again unless `store_all` is set. Changes the database was
+/// too busy for on an earlier poll are written first.
+/// 
+/// # Arguments
+/// 
+/// * `store` - The Store the changes are written to.
+/// * `paths` - A slice of paths to the repositories.
+/// * `authors` - The AuthorFilter describing whom to track.
+/// * `options` - The CollectOptions controlling what is counted.
+/// * `store_all` - Whether to store rows even if nothing changed.
+/// * `state` - The WatchState carried between polls, updated in place.
+async fn poll_repositories(
+    store: &dyn Store,
+    paths: &[PathBuf],
+    authors: &AuthorFilter,
+    options: &CollectOptions,
+    store_all: bool,
+    state: &mut WatchState,
+) {
+    state.flush_unsaved(store).await;
+    state.previous_total = Some(status::total(&state.repo_stats));
+    let mut opened = HashSet::new();
+    let mut matched_any = false;
+    let mut unmatched_authors = BTreeSet::new();
+    // Written together at the end, in a single transaction
+    let mut batch = Vec::new();
+
+    for path in paths {
+        let (repo, repo_name) = match open_repository(path) {
+            Ok(opened) => opened,
+            // A deleted or moved repository is reported once rather than on every poll
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                if state.missing.insert(path.clone()) {
+                    warn!(path = %path.display(), reason = e.message(), "repo removed: no longer watched until it is back");
+                }
+                continue;
+            }
+            Err(e) => {
+                warn!(path = %path.display(), reason = e.message(), "repo skipped: cannot open repository");
+                continue;
+            }
+        };
+        if state.missing.remove(path) {
+            info!(path = %path.display(), "repo is back: watching it again");
+        }
+        let repo_name = state.repo_names.get(path).cloned().unwrap_or(repo_name);
+        opened.insert(repo_name.clone());
+
+        // Without anyone to match commits against, only the pending changes are counted
+        let (collected, pending_only) = match authors.for_repository(&repo) {
+            Some(authors) => (collect_stats_cached(&repo, &authors, options, &mut state.commit_cache), false),
+            None => {
+                if state.warned_no_author.insert(path.clone()) {
+                    warn!(
+                        repo = %repo_name,
+                        author = UNKNOWN_AUTHOR,
+                        "commits skipped: no --author given and no user.name or user.email in its git config; \
+                         counting pending changes only"
+                    );
+                }
+                (collect_pending_stats(&repo, options), true)
+            }
+        };
+
+        match collected {
+            Ok(author_stats) => {
+                let timestamp = Utc::now();
+
+                let today = day_of(&timestamp.with_timezone(&Local), options.day_start_hour);
+                let branch = current_branch(&repo);
+
+                // Only worth walking again while nothing matched and nobody was told yet
+                if author_stats.values().any(|stats| !stats.commits.is_empty()) {
+                    matched_any = true;
+                } else if !matched_any && !state.warned_unmatched && !pending_only {
+                    unmatched_authors.extend(todays_authors(&repo, options).unwrap_or_default());
+                }
+
+                for commit in author_stats.values().flat_map(|stats| &stats.commits) {
+                    if commit.flagged && state.flagged_commits.insert(commit.sha.clone()) {
+                        info!(
+                            repo = %repo_name,
+                            commit = &commit.sha[..7.min(commit.sha.len())],
+                            net = commit.stats.committed_additions - commit.stats.committed_deletions,
+                            "large commit flagged: stored, but left out of the totals, the goal and the streak"
+                        );
+                    }
+                }
+                for (author, stats) in &author_stats {
+                    state.repo_stats.insert((repo_name.clone(), author.clone()), stats.clone());
+                    if stats.committed_additions > 0 {
+                        state.active_days.insert(today);
+                    }
+                    let snapshot = (today, branch.clone(), stats.clone());
+                    if !state.record_stored(&repo_name, author, snapshot) && !store_all {
+                        continue;
+                    }
+                    batch.extend(changes_from_stats(&repo_name, author, branch.as_deref(), stats, timestamp));
+                }
+            }
+            Err(e) => warn!(repo = %repo_name, reason = e.message(), "repo skipped: cannot read changes"),
+        }
+    }
+
+    state.save_changes(store, batch).await;
+
+    // Repositories that could not be opened no longer count in the totals
+    state.repo_stats.retain(|(repo_name, _), _| opened.contains(repo_name));
+    state.velocity.record(&status::total(&state.repo_stats), Utc::now());
+
+    // A misspelled author silently counts nothing, so say so once
+    if !matched_any && !unmatched_authors.is_empty() && !state.warned_unmatched {
+        state.warned_unmatched = true;
+        let seen: Vec<&str> = unmatched_authors.iter().map(String::as_str).collect();
+        warn!(
+            authors = %seen.join(", "),
+            "no commit from today matches the tracked authors; check --author for a misspelling"
+        );
+    }
+}
+
+/// Creates the id of a run of the tool, a random UUID.
+pub fn new_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Collects the identities tracked in any of the repositories, which are the given
+/// authors or, without any, the users set in the repositories' git config.
+fn tracked_identities(paths: &[PathBuf], authors: &AuthorFilter) -> BTreeSet<String> {
+    if !authors.is_empty() {
+        return authors.identities().cloned().collect();
+    }
+    paths
+        .iter()
+        .filter_map(|path| Repository::open(path).ok())
+        .filter_map(|repo| authors.for_repository(&repo).map(|filter| filter.identities().cloned().collect::<Vec<_>>()))
+        .flatten()
+        .collect()
+}
+
+/// Collects the names of the authors of today's commits on the tracked branch.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the Repository object.
+/// * `options` - A reference to the CollectOptions controlling which commits are walked.
+///
+/// # Returns
+///
+/// A Result containing the distinct author names.
+fn todays_authors(repo: &Repository, options: &CollectOptions) -> Result<BTreeSet<String>, git2::Error> {
+    let mut names = BTreeSet::new();
+    let revwalk = match walk_history(repo, options)? {
+        Some(revwalk) => revwalk,
+        None => return Ok(names),
+    };
+
+    for oid in revwalk.take(options.max_commits.unwrap_or(usize::MAX)) {
+        let commit = repo.find_commit(oid?)?;
+        let today = is_commit_from_today(&commit.time(), options.timezone, options.day_start_hour);
+        if today && !options.skips_commit(&commit) {
+            names.insert(String::from_utf8_lossy(commit.author().name_bytes()).into_owned());
+        }
+    }
+
+    Ok(names)
+}
+
+/// Opens the repository a path lies in, which may be any directory inside its
+/// working tree.
+///
+/// # Arguments
+///
+/// * `path` - A path inside the repository.
+///
+/// # Returns
+///
+/// A Result containing the Repository and its name, the name of its root directory.
+pub fn open_repository(path: &Path) -> Result<(Repository, String), git2::Error> {
+    let repo = Repository::discover(path)?;
+    let root = repo.workdir().unwrap_or_else(|| repo.path());
+    let repo_name = root.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    Ok((repo, repo_name))
+}
+
+/// Names the repositories of the given paths, so that each one is stored under a name of its own.
+///
+/// A repository is named by `--repo` or else by its root directory. When several
+/// repositories share a directory name, each is named by its parent and root
+/// directories instead, e.g. `org-a/api` and `org-b/api`.
+///
+/// # Arguments
+///
+/// * `paths` - The validated paths being tracked.
+/// * `named` - The names and paths given with `--repo`.
+///
+/// # Returns
+///
+/// A map from each path that can be opened to the name of its repository.
+fn repository_names(paths: &[PathBuf], named: &[(String, PathBuf)]) -> HashMap<PathBuf, String> {
+    let mut names = HashMap::new();
+    let mut roots: HashMap<PathBuf, (PathBuf, String)> = HashMap::new();
+    for path in paths {
+        // Validation may have canonicalized the path given with the name
+        let is_named = |named_path: &PathBuf| {
+            named_path == path || std::fs::canonicalize(named_path).is_ok_and(|resolved| &resolved == path)
+        };
+        if let Some((name, _)) = named.iter().find(|(_, named_path)| is_named(named_path)) {
+            names.insert(path.clone(), name.clone());
+        } else if let Ok((repo, repo_name)) = open_repository(path) {
+            let root = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+            roots.insert(path.clone(), (root, repo_name));
+        }
+    }
+
+    // Paths inside the same repository share its root, so only different roots collide
+    let mut roots_by_name: HashMap<&str, HashSet<&Path>> = HashMap::new();
+    for (root, repo_name) in roots.values() {
+        roots_by_name.entry(repo_name).or_default().insert(root);
+    }
+    let taken: HashSet<&String> = names.values().collect();
+    let mut derived = HashMap::new();
+    for (path, (root, repo_name)) in &roots {
+        let collides = roots_by_name[repo_name.as_str()].len() > 1 || taken.contains(repo_name);
+        let name = match root.parent().and_then(Path::file_name) {
+            Some(parent) if collides => format!("{}/{}", parent.to_string_lossy(), repo_name),
+            _ => repo_name.clone(),
+        };
+        derived.insert(path.clone(), name);
+    }
+
+    names.extend(derived);
+    names
+}
+
+/// Starts watching a repository for file events, limited to its subpaths if any are given.
+///
+/// # Arguments
+///
+/// * `watcher` - The watcher whose events trigger polls.
+/// * `path` - The path of the repository.
+/// * `subpaths` - The directories the counts are limited to, relative to the repository.
+///
+/// # Returns
+///
+/// A Result indicating whether every directory is watched.
+fn watch_repository(
+    watcher: &mut impl Watcher,
+    path: &Path,
+    subpaths: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if subpaths.is_empty() {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+    }
+    for subpath in subpaths {
+        let target = path.join(subpath);
+        watcher
+            .watch(&target, RecursiveMode::Recursive)
+            .map_err(|e| format!("cannot watch {}: {}", target.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Whether a watcher event can change the counts, so the repositories are
+/// polled again.
+///
+/// Inside the git directory only the index, HEAD and refs matter, and in the
+/// working tree files that git ignores, such as build output, do not.
+///
+/// # Arguments
+///
+/// * `event` - The event received from the watcher.
+/// * `paths` - The paths of the watched repositories.
+fn triggers_poll(event: &DebouncedEvent, paths: &[PathBuf]) -> bool {
+    match event {
+        // Each notice is followed by the debounced event itself
+        DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => false,
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Chmod(path)
+        | DebouncedEvent::Remove(path) => path_affects_counts(path, paths),
+        DebouncedEvent::Rename(from, to) => path_affects_counts(from, paths) || path_affects_counts(to, paths),
+        DebouncedEvent::Rescan | DebouncedEvent::Error(..) => true,
+    }
+}
+
+/// Takes the events already queued behind the one that triggered a poll, so a
+/// burst of saves is counted by a single poll instead of one each.
+///
+/// # Arguments
+///
+/// * `events` - The channel the watcher events arrive on.
+///
+/// # Returns
+///
+/// The number of events taken, which the coming poll covers.
+fn drain_pending_events(events: &mut tokio::sync::mpsc::UnboundedReceiver<DebouncedEvent>) -> usize {
+    let mut drained = 0;
+    while let Ok(event) = events.try_recv() {
+        if let DebouncedEvent::Error(e, path) = &event {
+            warn!(error = %e, path = ?path, "watch error");
+        }
+        drained += 1;
+    }
+    drained
+}
+
+/// Whether a change to a path inside one of the watched repositories can change its counts.
+fn path_affects_counts(path: &Path, paths: &[PathBuf]) -> bool {
+    // Anything unexpected is polled, so that a repository that is gone gets noticed
+    let repo = match paths.iter().find(|watched| path.starts_with(watched)).map(|watched| open_repository(watched)) {
+        Some(Ok((repo, _))) => repo,
+        _ => return true,
+    };
+
+    if let Ok(relative) = path.strip_prefix(repo.path()) {
+        return relative == Path::new("index")
+            || relative == Path::new("HEAD")
+            || relative == Path::new("packed-refs")
+            || relative.starts_with("refs");
+    }
+    match repo.workdir().and_then(|workdir| path.strip_prefix(workdir).ok()) {
+        Some(relative) => !repo.is_path_ignored(relative).unwrap_or(false),
+        None => true,
+    }
+}
+
+/// Checks that every path lies inside a git repository.
+/// 
+/// Canonical paths have their symlinks resolved, so a repository reached through
+/// a link is watched and named like its real directory, and listed only once.
+/// 
+/// # Arguments
+/// 
+/// * `paths` - The paths to validate.
+/// * `skip_invalid` - Whether to warn about and drop invalid paths instead of failing.
+/// * `canonicalize` - Whether to replace each path by its canonical form.
+/// 
+/// # Returns
+/// 
+/// A Result containing the valid paths, or an error naming the invalid ones.
+fn validate_repositories(
+    paths: Vec<PathBuf>,
+    skip_invalid: bool,
+    canonicalize: bool,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut valid = Vec::new();
+    let mut invalid = Vec::new();
+
+    for path in paths {
+        // A path that cannot be resolved does not exist, which discovery reports below
+        let path = match canonicalize {
+            true => std::fs::canonicalize(&path).unwrap_or(path),
+            false => path,
+        };
+        match Repository::discover(&path) {
+            Ok(_) if valid.contains(&path) => {}
+            Ok(_) => valid.push(path),
+            Err(e) => {
+                warn!(path = %path.display(), reason = e.message(), "repo skipped: not a git repository");
+                invalid.push(path);
+            }
+        }
+    }
+
+    if !invalid.is_empty() && !skip_invalid {
+        return Err(format!(
+            "{} path(s) are not git repositories; pass --skip-invalid to ignore them",
+            invalid.len()
+        )
+        .into());
+    }
+    if valid.is_empty() {
+        return Err("no git repositories to watch".into());
+    }
+
+    Ok(valid)
+}
+
+/// Watches the specified repositories for changes and updates the database accordingly.
+/// 
+/// On Ctrl-C a final poll is stored before the database is closed. With `--once`
+/// that poll is the only one.
+/// 
+/// # Arguments
+/// 
+/// * `opt` - The parsed watch options.
+/// 
+/// # Returns
+/// 
+/// A Result indicating success or failure.
+async fn watch_repositories(mut opt: WatchOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let authors = opt.author_filter()?;
+    let options = opt.collect_options();
+    let store_all = opt.store_all;
+    let mut paths = std::mem::take(&mut opt.paths);
+    paths.extend(opt.named_repos.iter().map(|(_, path)| path.clone()));
+    for root in &opt.discover {
+        let found = discover::discover_repositories(root, opt.discover_depth, opt.include_nested)
+            .map_err(|e| format!("cannot discover repositories under {}: {}", root.display(), e))?;
+        for repo in found {
+            info!(root = %root.display(), repo = %repo.display(), "discovered repository");
+            if !paths.contains(&repo) {
+                paths.push(repo);
+            }
+        }
+    }
+    let mut paths = validate_repositories(paths, opt.skip_invalid, !opt.no_canonicalize)?;
+
+    // Named over every repository, so disabling one never renames another
+    let mut repo_names = repository_names(&paths, &opt.named_repos);
+    let disabled = config::disabled_repos(&opt.repo_configs, &repo_names, &opt.disable, &opt.enable)?;
+    paths.retain(|path| !disabled.contains(path));
+    if paths.is_empty() {
+        return Err("every repository is disabled; turn one back on with --enable".into());
+    }
+    if !opt.quiet && !opt.tui && opt.format == OutputFormat::Text {
+        let names = |paths: Vec<&PathBuf>| {
+            let names: BTreeSet<&str> = paths.into_iter().map(|path| repo_names[path].as_str()).collect();
+            names.into_iter().collect::<Vec<_>>().join(", ")
+        };
+        match disabled.is_empty() {
+            true => println!("Tracking: {}", names(paths.iter().collect())),
+            false => {
+                let (active, disabled) = (names(paths.iter().collect()), names(disabled.iter().collect()));
+                println!("Tracking: {}; disabled: {}", active, disabled)
+            }
+        }
+    }
+
+    if opt.snapshot_db_every.is_some() && store::is_postgres_url(opt.db_path()) {
+        return Err("--snapshot-db-every needs a SQLite database; back Postgres up with pg_dump".into());
+    }
+    let store: Box<dyn Store> = match opt.dry_run {
+        true => {
+            warn!(db_path = opt.db_path(), "DRY RUN — not writing to DB");
+            store::connect_read_only(opt.db_path()).await?
+        }
+        false => store::connect(opt.db_path()).await?,
+    };
+    if !opt.no_wal && !opt.dry_run {
+        store.enable_wal().await?;
+    }
+
+    store.setup().await?;
+
+    let session_id = opt.session_id.clone().unwrap_or_else(new_session_id);
+    if opt.catch_up {
+        let caught_up = backfill::catch_up(store.as_ref(), &paths, &repo_names, &authors, &options, &session_id).await?;
+        info!(commits = caught_up, "catch-up done");
+    }
+    let day_start_hour = opt.day_start_hour;
+    let today = day_of(&Local::now(), day_start_hour);
+    if let Some(retain_days) = opt.retain_days {
+        rollup::prune_old_changes(store.as_ref(), retain_days, opt.rollup_pruned, today, day_start_hour).await?;
+    }
+
+    // Show what was stored today until the first poll replaces it
+    let mut repo_stats = report::load_latest_stats(store.as_ref(), today, day_start_hour).await?;
+    let mut display = opt.display_options(&authors);
+    display.repo_styles = config::repo_styles(&opt.repo_configs, &repo_names);
+    repo_names.retain(|path, _| !disabled.contains(path));
+    let print_status = |state: &WatchState| match (opt.quiet, opt.compact) {
+        (true, _) => {}
+        (false, true) => {
+            let previous = state.previous_total.as_ref();
+            let pace = state.velocity.loc_per_minute(display.deletions_as);
+            print!("{}", status::render_compact(&state.repo_stats, previous, &display, Utc::now(), pace))
+        }
+        (false, false) => {
+            let pace = state.velocity.loc_per_minute(display.deletions_as);
+            print!("{}", status::render(&state.repo_stats, &display, Utc::now(), state.streak(), pace))
+        }
+    };
+    let tracked_names: HashSet<&String> = repo_names.values().collect();
+    let identities = tracked_identities(&paths, &authors);
+    repo_stats.retain(|(repo_name, author), _| tracked_names.contains(repo_name) && identities.contains(author));
+    let mut state = WatchState {
+        repo_stats,
+        repo_names,
+        active_days: streak::active_days(store.as_ref(), identities.iter(), day_start_hour).await?,
+        milestones: opt.notify_milestone.map(milestone::MilestoneTracker::new),
+        idle: opt.idle_minutes.map(|minutes| idle::IdleTracker::new(minutes, Utc::now())),
+        session_id: Some(session_id),
+        day_start_hour,
+        ..Default::default()
+    };
+    info!(session_id = state.session_id.as_deref(), "session started");
+
+    if opt.once {
+        poll_repositories(store.as_ref(), &paths, &authors, &options, store_all, &mut state)
+            .instrument(info_span!("poll", repos = paths.len()))
+            .await;
+        let flushed = state.flush_before_exit(store.as_ref()).await;
+        store.close().await;
+        print_status(&state);
+        flushed?;
+        return Ok(());
+    }
+
+    let metrics = match &opt.metrics_addr {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            let metrics = metrics::Metrics::default();
+            let server = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(listener, server).await {
+                    error!(error = %e, "metrics server stopped");
+                }
+            });
+            metrics.update(&state.repo_totals());
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    // Replies to `stats` in the format of `--format json`, whatever the printout uses
+    let json_display = DisplayOptions { format: OutputFormat::Json, ..display.clone() };
+    let json_status =
+        |state: &WatchState| status::render(&state.repo_stats, &json_display, Utc::now(), state.streak(), None);
+    let on_update = opt
+        .on_update
+        .as_deref()
+        .map(|command| hook::UpdateHook::parse(command, opt.on_update_shell))
+        .transpose()?;
+    let control = match &opt.control_socket {
+        Some(path) => {
+            let listener = control::bind(path)
+                .map_err(|e| format!("cannot create control socket {}: {}", path.display(), e))?;
+            let control = control::Control::default();
+            let server = control.clone();
+            tokio::spawn(async move {
+                if let Err(e) = control::serve(listener, server).await {
+                    error!(error = %e, "control socket stopped");
+                }
+            });
+            control.update(json_status(&state));
+            Some(control)
+        }
+        None => None,
+    };
+
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, Duration::from_secs(opt.interval_secs()))?;
+
+    for path in &paths {
+        watch_repository(&mut watcher, path, &options.subpaths)?;
+    }
+    let mut unwatched: HashSet<PathBuf> = HashSet::new();
+
+    // Forward watcher events onto an async channel so they can be raced against Ctrl-C
+    let (event_tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for event in rx {
+            if event_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut current_day = state.today();
+    let mut dashboard = None;
+    let mut shutdown: std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>>>> = if opt.tui {
+        let (mut started, mut quit) = tui::Dashboard::start(display.clone())?;
+        started.draw(&state.repo_totals(), None, state.streak(), None)?;
+        dashboard = Some(started);
+        Box::pin(async move {
+            quit.recv().await;
+            Ok(())
+        })
+    } else {
+        if !state.repo_stats.is_empty() {
+            print_status(&state);
+        }
+        Box::pin(tokio::signal::ctrl_c())
+    };
+
+    // A dry run writes no files, snapshots included
+    let mut snapshots = match (opt.snapshot_db_every, opt.dry_run) {
+        (Some(minutes), false) => {
+            let every = Duration::from_secs(u64::from(minutes) * 60);
+            Some(tokio::time::interval_at(tokio::time::Instant::now() + every, every))
+        }
+        _ => None,
+    };
+
+    let mut fetches =
+        opt.fetch_every.map(|minutes| tokio::time::interval(Duration::from_secs(u64::from(minutes) * 60)));
+    let remote = fetch::remote_name(opt.remote_branch.as_deref()).to_string();
+    // Fetches run detached so a hung remote can't hold up polling or Ctrl-C, one at a time
+    let (fetch_done, mut fetch_events) = tokio::sync::mpsc::unbounded_channel();
+    let mut fetching = false;
+
+    // While idle, events only schedule a poll, run once the idle poll interval has passed
+    let mut deferred_poll: Option<tokio::time::Instant> = None;
+    loop {
+        let deferred = deferred_poll.unwrap_or_else(tokio::time::Instant::now);
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => {
+                    if let DebouncedEvent::Error(e, path) = &event {
+                        warn!(error = %e, path = ?path, "watch error");
+                    }
+                    if !triggers_poll(&event, &paths) {
+                        debug!(event = ?event, "event ignored: cannot change the counts");
+                        continue;
+                    }
+                    if state.idle.as_ref().is_some_and(idle::IdleTracker::is_idle) {
+                        deferred_poll.get_or_insert_with(|| tokio::time::Instant::now() + idle::IDLE_POLL_INTERVAL);
+                        continue;
+                    }
+                    let coalesced = drain_pending_events(&mut events);
+                    if coalesced > 0 {
+                        debug!(events = coalesced + 1, "events coalesced into one poll");
+                    }
+                }
+                None => {
+                    drop(dashboard);
+                    store.close().await;
+                    return Err("file watcher stopped unexpectedly".into());
+                }
+            },
+            _ = tokio::time::sleep_until(deferred), if deferred_poll.is_some() => {}
+            _ = async { snapshots.as_mut().expect("only polled with snapshots").tick().await },
+                if snapshots.is_some() => {
+                let path = store::snapshot_path(opt.db_path(), opt.snapshot_dir.as_deref(), Local::now());
+                match store.snapshot(&path).await {
+                    Ok(()) => info!(path = %path.display(), "database snapshot written"),
+                    Err(e) => error!(path = %path.display(), error = %e, "error writing database snapshot"),
+                }
+                continue;
+            }
+            // The first tick is immediate, so the remotes are fetched as the watcher starts
+            _ = async { fetches.as_mut().expect("only polled with fetches").tick().await }, if fetches.is_some() => {
+                if fetching {
+                    debug!("the previous fetch is still running, skipping this one");
+                    continue;
+                }
+                fetching = true;
+                fetch::spawn_fetch_all(paths.clone(), remote.clone(), fetch::FETCH_TIMEOUT, fetch_done.clone());
+                continue;
+            }
+            // Once a fetch returns, the loop polls so the commits fetched are counted
+            Some(event) = fetch_events.recv() => match event {
+                fetch::FetchEvent::TimedOut => {
+                    warn!(timeout = ?fetch::FETCH_TIMEOUT, "fetch still running, skipping fetches until it returns");
+                    continue;
+                }
+                fetch::FetchEvent::Finished => fetching = false,
+            },
+            result = &mut shutdown => {
+                result?;
+                drop(dashboard);
+                // Kept out of the JSON stream, which carries one stats object per line
+                if !opt.quiet && opt.format == OutputFormat::Text {
+                    println!("Flushing and exiting...");
+                }
+                poll_repositories(store.as_ref(), &paths, &authors, &options, store_all, &mut state)
+                    .instrument(info_span!("poll", repos = paths.len(), final_poll = true))
+                    .await;
+                let flushed = state.flush_before_exit(store.as_ref()).await;
+                print_status(&state);
+                store.close().await;
+                if let Some(path) = &opt.control_socket {
+                    let _ = std::fs::remove_file(path);
+                }
+                flushed?;
+                return Ok(());
+            }
+        }
+        deferred_poll = None;
+
+        // Summarize the previous day once the first poll after the day starts arrives
+        let today = state.today();
+        if today != current_day {
+            if let Err(e) = rollup::rollup_day(store.as_ref(), current_day, day_start_hour).await {
+                error!(day = %current_day, error = %e, "error rolling up day");
+            }
+            if let Some(retain_days) = opt.retain_days {
+                let (roll_up, start) = (opt.rollup_pruned, day_start_hour);
+                if let Err(e) = rollup::prune_old_changes(store.as_ref(), retain_days, roll_up, today, start).await {
+                    error!(error = %e, "error pruning old changes");
+                }
+            }
+            current_day = today;
+        }
+
+        let before = state.repo_stats.clone();
+        poll_repositories(store.as_ref(), &paths, &authors, &options, store_all, &mut state)
+            .instrument(info_span!("poll", repos = paths.len()))
+            .await;
+        if let Some(idle) = &mut state.idle {
+            idle.record_poll(state.repo_stats != before, Utc::now());
+        }
+
+        // Stop watching repositories that disappeared and watch those that came back
+        for path in &state.missing {
+            if unwatched.insert(path.clone()) {
+                let _ = watcher.unwatch(path);
+            }
+        }
+        for path in unwatched.clone().difference(&state.missing) {
+            match watch_repository(&mut watcher, path, &options.subpaths) {
+                Ok(()) => {
+                    unwatched.remove(path);
+                }
+                Err(e) => warn!(path = %path.display(), error = %e, "cannot watch repository again"),
+            }
+        }
+
+        if let Some(metrics) = &metrics {
+            metrics.update(&state.repo_totals());
+        }
+        if let Some(control) = &control {
+            control.update(json_status(&state));
+        }
+        if let Some(on_update) = &on_update {
+            on_update.run(json_status(&state));
+        }
+        if let Some((reached, repos)) = state.crossed_milestone(display.deletions_as) {
+            let who = authors.owner().map(String::as_str).unwrap_or("You");
+            let message = milestone::message(who, reached, repos);
+            if let Some(webhook) = &opt.slack_webhook {
+                milestone::post_to_slack(webhook.clone(), message.clone());
+            }
+            milestone::notify_desktop(message);
+        }
+        match &mut dashboard {
+            Some(dashboard) => {
+                let pace = state.velocity.loc_per_minute(display.deletions_as);
+                dashboard.draw(&state.repo_totals(), Some(Local::now()), state.streak(), pace)?
+            }
+            None => print_status(&state),
+        }
+    }
+}
+
+impl Opt {
+    /// The most verbose log level to print and whether to print JSON lines,
+    /// which only `watch` lets you choose.
+    pub fn log_settings(&self) -> (Level, bool) {
+        match self {
+            Opt::Watch(watch) => (watch.log_level, watch.log_json),
+            _ => (Level::INFO, false),
+        }
+    }
+}
+
+/// Runs a subcommand of the command line.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed command line.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: Opt) -> Result<(), Box<dyn std::error::Error>> {
+    match opt {
+        Opt::Watch(mut opt) => {
+            opt.apply_config(config::load(opt.config.as_deref())?)?;
+            watch_repositories(opt).await
+        }
+        Opt::Report(opt) => report::run(opt).await,
+        Opt::Rollup(opt) => rollup::run(opt).await,
+        Opt::Export(opt) => export::run(opt).await,
+        Opt::Backfill(opt) => backfill::run(opt).await,
+        Opt::Streak(opt) => streak::run(opt).await,
+        Opt::Hourly(opt) => hourly::run(opt).await,
+        Opt::Lifetime(opt) => lifetime::run(opt).await,
+        Opt::Compare(opt) => compare::run(opt).await,
+        Opt::TopFiles(opt) => top_files::run(opt).await,
+        Opt::Authors(opt) => authors::run(opt).await,
+    }
+}
+
+#[cfg(test)]
+impl RepoStats {
+    /// Stats made of a single commit with the given counts.
+    fn with_commit(sha: &str, timestamp: DateTime<Utc>, additions: i32, deletions: i32) -> Self {
+        let stats = RepoStats { committed_additions: additions, committed_deletions: deletions, ..Default::default() };
+        let commit = CommitStats {
+            sha: sha.to_string(),
+            author_email: None,
+            timestamp,
+            stats: stats.clone(),
+            files: Vec::new(),
+            flagged: false,
+            is_merge: false,
+        };
+        RepoStats { commits: vec![commit], ..stats }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Oid, Signature};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Creates an empty repository in a fresh temporary directory.
+    fn init_repo() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    /// Writes `contents` to `name` relative to the repository's working directory.
+    fn write_file(repo: &Repository, name: &str, contents: &str) {
+        let path = repo.workdir().unwrap().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    /// Stages every file in the working directory and commits it on HEAD.
+    fn commit_all(repo: &Repository, author: &str, time: Time, message: &str) -> Oid {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::new(author, &format!("{}@example.com", author), &time).unwrap();
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => Vec::new(),
+        };
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parent_refs)
+            .unwrap()
+    }
+
+    /// Commits the current index with explicit parents, moving HEAD to the result.
+    fn commit_with_parents(
+        repo: &Repository,
+        author: &str,
+        time: Time,
+        message: &str,
+        parents: &[Oid],
+    ) -> Oid {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::new(author, &format!("{}@example.com", author), &time).unwrap();
+        let parents: Vec<git2::Commit> = parents
+            .iter()
+            .map(|oid| repo.find_commit(*oid).unwrap())
+            .collect();
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        let oid = repo
+            .commit(None, &sig, &sig, message, &tree, &parent_refs)
+            .unwrap();
+        repo.reference("refs/heads/master", oid, true, message).unwrap();
+        repo.set_head("refs/heads/master").unwrap();
+        oid
+    }
+
+    /// The filter most tests collect stats for.
+    fn me() -> AuthorFilter {
+        AuthorFilter::new(vec!["Me".to_string()], Vec::new(), Default::default())
+    }
+
+    /// A commit time in the local offset, so its date matches the local today.
+    fn now() -> Time {
+        days_ago(0)
+    }
+
+    fn days_ago(days: i64) -> Time {
+        let offset_minutes = Local::now().offset().local_minus_utc() / 60;
+        Time::new(Utc::now().timestamp() - days * 86_400, offset_minutes)
+    }
+
+    fn lines(count: usize) -> String {
+        (0..count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn pending_changes_are_counted_once_across_files() {
+        let (_dir, repo) = init_repo();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            write_file(&repo, name, &lines(3));
+        }
+        commit_all(&repo, "Other", now(), "initial");
+
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            write_file(&repo, name, &lines(5));
+        }
+
+        let diff = repo.diff_index_to_workdir(None, None).unwrap();
+        let expected = diff.stats().unwrap();
+        assert_eq!(expected.insertions(), 6);
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.pending_additions, expected.insertions() as i32);
+        assert_eq!(stats.pending_deletions, expected.deletions() as i32);
+    }
+
+    #[test]
+    fn binary_files_count_only_with_count_binary() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+
+        // A NUL byte marks the content as binary, however many of its 502 lines are text
+        let image: Vec<u8> = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".iter().chain(&[b'\n'; 500]).copied().collect();
+        fs::write(dir.path().join("logo.png"), &image).unwrap();
+        write_file(&repo, "notes.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "add logo");
+        fs::write(dir.path().join("banner.png"), &image).unwrap();
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 3);
+        assert_eq!(stats.pending_additions, 0);
+
+        let options = CollectOptions { count_binary: true, by_language: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 3 + 502);
+        assert_eq!(stats.pending_additions, 502);
+        assert_eq!(stats.extensions["png"].committed_additions, 502);
+    }
+
+    #[test]
+    fn branch_option_counts_that_branch_instead_of_head() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        let initial = commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "b.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "on master");
+
+        // Check out a feature branch started before the master commit
+        repo.branch("feature", &repo.find_commit(initial).unwrap(), false).unwrap();
+        repo.set_head("refs/heads/feature").unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        write_file(&repo, "c.txt", &lines(5));
+        commit_all(&repo, "Me", now(), "on feature");
+
+        let committed = |branch: Option<&str>| {
+            let options = CollectOptions { branch: branch.map(str::to_string), ..Default::default() };
+            collect_stats(&repo, &me(), &options).unwrap()["Me"].committed_additions
+        };
+        assert_eq!(committed(None), 5);
+        assert_eq!(committed(Some("master")), 3);
+        // A branch that does not exist falls back to HEAD
+        assert_eq!(committed(Some("missing")), 5);
+    }
+
+    #[test]
+    fn staged_and_unstaged_edits_are_counted_apart() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "staged.txt", &lines(5));
+        write_file(&repo, "unstaged.txt", &lines(5));
+        commit_all(&repo, "Other", now(), "initial");
+
+        write_file(&repo, "staged.txt", &lines(8));
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+        write_file(&repo, "unstaged.txt", &lines(6));
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!((stats.staged_additions, stats.staged_deletions), (3, 0));
+        assert_eq!((stats.pending_additions, stats.pending_deletions), (4, 0));
+        assert_eq!(stats.unstaged_loc(), 1);
+
+        let changes = changes_from_stats("api", "Me", None, stats, Utc::now());
+        assert_eq!((changes[0].additions, changes[0].staged_additions), (4, 3));
+
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(("api".to_string(), "Me".to_string()), stats.clone());
+        let output = status::render(&repo_stats, &Default::default(), Utc::now(), 0, None);
+        assert!(output.starts_with("api:   0 LoC committed, 4 LoC In Progress (3 staged, 1 unstaged)\n"));
+    }
+
+    #[test]
+    fn hunks_tell_new_code_from_edits_of_existing_code() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(20));
+        commit_all(&repo, "Other", now(), "initial");
+
+        // Three lines inserted after `line 2`, far enough from the rewrite of `line 15` to be a hunk of their own
+        let mut contents: Vec<String> = lines(20).lines().map(str::to_string).collect();
+        contents[15] = "line 15, rewritten".to_string();
+        contents.insert(16, "line 15 and a half".to_string());
+        for (offset, line) in ["new a", "new b", "new c"].into_iter().enumerate() {
+            contents.insert(3 + offset, line.to_string());
+        }
+        write_file(&repo, "a.txt", &(contents.join("\n") + "\n"));
+        commit_all(&repo, "Me", now(), "insert and edit");
+
+        let options = CollectOptions { by_hunk: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!((stats.committed_additions, stats.committed_deletions), (5, 1));
+        assert_eq!((stats.committed_inserted, stats.committed_edited), (3, 2));
+        let row = &changes_from_stats("repo", "Me", None, stats, Utc::now())[1];
+        assert_eq!((row.inserted_lines, row.edited_lines), (3, 2));
+
+        // Without asking, the additions are not split
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!((stats.committed_additions, stats.committed_inserted, stats.committed_edited), (5, 0, 0));
+
+        // The rows of the breakdown carry the split of their own files
+        write_file(&repo, "b.md", "new doc\n");
+        commit_all(&repo, "Me", now(), "document");
+        let options = CollectOptions { by_hunk: true, by_language: true, by_category: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        let changes = changes_from_stats("repo", "Me", None, stats, Utc::now());
+        let split = |extension: Option<&str>, category: Option<&str>| {
+            let rows: Vec<&LocChange> = changes
+                .iter()
+                .filter(|row| row.is_committed)
+                .filter(|row| row.extension.as_deref() == extension && row.category.as_deref() == category)
+                .collect();
+            (rows.iter().map(|row| row.inserted_lines).sum(), rows.iter().map(|row| row.edited_lines).sum())
+        };
+        assert_eq!(split(Some("txt"), None), (3, 2));
+        assert_eq!(split(Some("md"), None), (1, 0));
+        assert_eq!(split(None, Some("docs")), (4, 2));
+        assert_eq!(split(None, None), (4, 2));
+    }
+
+    #[test]
+    fn files_changed_are_counted_once_per_file() {
+        let (_dir, repo) = init_repo();
+        for name in ["a.txt", "b.txt", "c.rs"] {
+            write_file(&repo, name, &lines(2));
+        }
+        commit_all(&repo, "Other", now(), "initial");
+
+        for name in ["a.txt", "b.txt", "c.rs"] {
+            write_file(&repo, name, &lines(4));
+        }
+        commit_all(&repo, "Me", now(), "three files");
+        // Staged and unstaged edits to the same file make one pending file
+        write_file(&repo, "a.txt", &lines(5));
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        write_file(&repo, "a.txt", &lines(6));
+        write_file(&repo, "c.rs", &lines(5));
+
+        let options = CollectOptions { by_language: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!((stats.committed_files, stats.pending_files), (3, 2));
+        assert_eq!((stats.extensions["txt"].committed_files, stats.extensions["rs"].pending_files), (2, 1));
+
+        let changes = changes_from_stats("api", "Me", None, stats, Utc::now());
+        let commit_row = changes.iter().find(|change| change.is_committed && change.is_total()).unwrap();
+        assert_eq!(commit_row.files_changed, 3);
+        assert_eq!(changes[0].files_changed, 2);
+    }
+
+    #[test]
+    fn reindented_lines_count_only_without_ignore_whitespace() {
+        let (_dir, repo) = init_repo();
+        let code: String = (0..10).map(|i| format!("call({});\n", i)).collect();
+        write_file(&repo, "main.c", &code);
+        commit_all(&repo, "Other", now(), "initial");
+
+        let indented: String = code.lines().map(|line| format!("    {}\n", line)).collect();
+        write_file(&repo, "main.c", &indented);
+        commit_all(&repo, "Me", now(), "indent");
+        let pending: String = indented.lines().map(|line| format!("\t{}\n", line.trim_start())).collect();
+        write_file(&repo, "main.c", &pending);
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!((stats.committed_additions, stats.committed_deletions), (10, 10));
+        assert_eq!((stats.pending_additions, stats.pending_deletions), (10, 10));
+
+        let options = CollectOptions { ignore_whitespace: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!((stats.committed_additions, stats.committed_deletions), (0, 0));
+        assert_eq!((stats.pending_additions, stats.pending_deletions), (0, 0));
+    }
+
+    #[test]
+    fn blank_and_comment_lines_count_only_without_significant_lines_only() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "main.rs", "fn main() {}\n");
+        commit_all(&repo, "Other", now(), "initial");
+
+        // Three lines of code, two blank ones and three comments
+        let code = "fn main() {}\n\n// Greets\n#[inline]\nfn greet() {\n    # a comment\n\n    -- and another\n}\n";
+        write_file(&repo, "main.rs", code);
+        commit_all(&repo, "Me", now(), "greet");
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 8);
+
+        let options = CollectOptions { significant_lines_only: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 3);
+        assert_eq!(stats.commits[0].files[0].additions, 3);
+    }
+
+    #[test]
+    fn a_moved_submodule_counts_only_with_count_submodules() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+
+        let mut submodule = repo.submodule("https://example.com/lib.git", Path::new("lib"), true).unwrap();
+        let lib = submodule.open().unwrap();
+        write_file(&lib, "lib.rs", &lines(2));
+        commit_all(&lib, "Other", now(), "lib");
+        submodule.add_finalize().unwrap();
+        commit_all(&repo, "Other", now(), "add lib");
+
+        // Working on the submodule moves its HEAD away from the commit recorded
+        write_file(&lib, "lib.rs", &lines(6));
+        commit_all(&lib, "Me", now(), "more lib");
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!((stats.pending_additions, stats.pending_deletions), (0, 0));
+
+        let options = CollectOptions { count_submodules: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!((stats.pending_additions, stats.pending_deletions), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn stored_rows_carry_the_author_email() {
+        let (_dir, repo) = init_repo();
+        repo.config().unwrap().set_str("user.email", "me@laptop.example.com").unwrap();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Me", now(), "more");
+        write_file(&repo, "a.txt", &lines(6));
+
+        let store = store::SqliteStore::memory().await;
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        for change in changes_from_stats("api", "Me", None, stats, Utc::now()) {
+            store.store_change(&change).await.unwrap();
+        }
+        let stored = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let committed = stored.iter().find(|change| change.is_committed).unwrap();
+        assert_eq!(committed.author.as_deref(), Some("Me"));
+        assert_eq!(committed.author_email.as_deref(), Some("Me@example.com"));
+        // Without a configured email, pending work falls back to the git config
+        let pending = stored.iter().find(|change| !change.is_committed).unwrap();
+        assert_eq!(pending.author_email.as_deref(), Some("me@laptop.example.com"));
+
+        let authors = AuthorFilter::new(vec!["Me".to_string()], vec!["me@work.example.com".to_string()], Default::default());
+        let stats = &collect_stats(&repo, &authors, &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.pending_email.as_deref(), Some("me@work.example.com"));
+    }
+
+    #[test]
+    fn a_second_poll_reuses_the_counts_of_seen_commits() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        for count in [2, 3] {
+            write_file(&repo, "a.txt", &lines(count));
+            commit_all(&repo, "Me", now(), "one more line");
+        }
+
+        let mut cache = CommitCache::new();
+        let options = CollectOptions::default();
+        let first = collect_stats_cached(&repo, &me(), &options, &mut cache).unwrap();
+        assert_eq!(cache.diffed(), 2);
+
+        let second = collect_stats_cached(&repo, &me(), &options, &mut cache).unwrap();
+        assert_eq!(cache.diffed(), 2);
+        assert_eq!(second["Me"].committed_additions, first["Me"].committed_additions);
+        assert_eq!(second["Me"].commits.len(), 2);
+
+        // Only the commit made since is diffed
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Me", now(), "one more line");
+        let third = collect_stats_cached(&repo, &me(), &options, &mut cache).unwrap();
+        assert_eq!(cache.diffed(), 3);
+        assert_eq!(third["Me"].committed_additions, 3);
+    }
+
+    #[test]
+    fn the_walk_stops_after_max_commits() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", days_ago(1), "initial");
+        // Distinct commit times keep the newest-first order of the walk stable
+        let start = now();
+        for count in [2, 3, 4] {
+            write_file(&repo, "a.txt", &lines(count));
+            let time = Time::new(start.seconds() + count as i64, start.offset_minutes());
+            commit_all(&repo, "Me", time, "one more line");
+        }
+
+        let committed = |max_commits: Option<usize>| {
+            let options = CollectOptions { max_commits, ..Default::default() };
+            let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+            (stats.commits.len(), stats.committed_additions)
+        };
+        assert_eq!(committed(None), (3, 3));
+        assert_eq!(committed(Some(2)), (2, 2));
+        assert_eq!(committed(Some(0)), (0, 0));
+    }
+
+    #[test]
+    fn deleted_files_count_as_pending_deletions_only() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "doomed.txt", &lines(20));
+        write_file(&repo, "kept.txt", &lines(7));
+        write_file(&repo, "other.txt", &lines(4));
+        commit_all(&repo, "Other", now(), "initial");
+
+        fs::remove_file(dir.path().join("doomed.txt")).unwrap();
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.pending_deletions, 20);
+        assert_eq!(stats.pending_additions, 0);
+    }
+
+    #[test]
+    fn todays_commits_after_an_old_merge_parent_are_counted() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "base.txt", &lines(1));
+        let base = commit_with_parents(&repo, "Other", days_ago(10), "base", &[]);
+
+        write_file(&repo, "side.txt", &lines(4));
+        let side = commit_with_parents(&repo, "Other", days_ago(5), "side", &[base]);
+
+        // Rewind the working tree to base before building the main line
+        fs::remove_file(repo.workdir().unwrap().join("side.txt")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("side.txt")).unwrap();
+        index.write().unwrap();
+
+        write_file(&repo, "early.txt", &lines(2));
+        let early = commit_with_parents(&repo, "Me", now(), "early", &[base]);
+
+        // An imported commit carrying an old timestamp on top of today's work
+        write_file(&repo, "imported.txt", &lines(7));
+        let imported = commit_with_parents(&repo, "Other", days_ago(3), "imported", &[early]);
+
+        write_file(&repo, "side.txt", &lines(4));
+        let merge = commit_with_parents(&repo, "Other", now(), "merge", &[imported, side]);
+
+        write_file(&repo, "after.txt", &lines(3));
+        commit_with_parents(&repo, "Me", now(), "after", &[merge]);
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 5);
+        assert_eq!(stats.committed_deletions, 0);
+    }
+
+    #[test]
+    fn stats_split_into_committed_and_pending_rows() {
+        let polled = Utc::now();
+        let committed_at = polled - chrono::Duration::hours(1);
+        let stats = RepoStats {
+            pending_additions: 4,
+            pending_deletions: 1,
+            ..RepoStats::with_commit("a1b2", committed_at, 10, 2)
+        };
+
+        let changes = changes_from_stats("repo", "Me", None, &stats, polled);
+        let [pending, committed] = &changes[..] else { panic!("expected two rows") };
+        assert!(!pending.is_committed);
+        assert_eq!((pending.additions, pending.deletions), (4, 1));
+        assert_eq!((pending.timestamp, pending.commit_sha.as_deref()), (polled, None));
+        assert!(committed.is_committed);
+        assert_eq!((committed.additions, committed.deletions), (10, 2));
+        assert_eq!((committed.timestamp, committed.commit_sha.as_deref()), (committed_at, Some("a1b2")));
+    }
+
+    #[test]
+    fn commits_are_attributed_to_each_matching_author() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "alice.txt", &lines(4));
+        commit_all(&repo, "Alice", now(), "alice");
+        write_file(&repo, "bob.txt", &lines(2));
+        commit_all(&repo, "Bob", now(), "bob");
+        write_file(&repo, "carol.txt", &lines(9));
+        commit_all(&repo, "Carol", now(), "carol");
+        write_file(&repo, "alice.txt", &lines(1));
+
+        let authors = AuthorFilter::new(
+            vec!["Alice".to_string(), "Bob".to_string()],
+            Vec::new(),
+            Default::default(),
+        );
+        let stats = collect_stats(&repo, &authors, &CollectOptions::default()).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["Alice"].committed_additions, 4);
+        assert_eq!(stats["Alice"].pending_deletions, 3);
+        assert_eq!(stats["Bob"].committed_additions, 2);
+        assert_eq!(stats["Bob"].pending_deletions, 0);
+
+        let committed = &changes_from_stats("repo", "Bob", None, &stats["Bob"], Utc::now())[0];
+        assert_eq!(committed.author.as_deref(), Some("Bob"));
+    }
+
+    #[test]
+    fn commits_match_by_email_when_the_name_differs() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "laptop.txt", &lines(6));
+        // commit_all derives the email from the name, so this is laptop@example.com
+        commit_all(&repo, "laptop", now(), "from another machine");
+
+        let authors = AuthorFilter::new(
+            vec!["Swifty".to_string()],
+            vec!["Laptop@Example.com".to_string()],
+            Default::default(),
+        );
+        let stats = collect_stats(&repo, &authors, &CollectOptions::default()).unwrap();
+        assert_eq!(stats["Laptop@Example.com"].committed_additions, 6);
+        assert_eq!(stats["Swifty"].committed_additions, 0);
+    }
+
+    #[test]
+    fn aliases_in_the_author_map_count_as_one_author() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Swifty", now(), "home");
+        write_file(&repo, "b.txt", &lines(2));
+        commit_all(&repo, "swiftyos", now(), "laptop");
+        write_file(&repo, "c.txt", &lines(4));
+        // Only the email, work@example.com, is in the map
+        commit_all(&repo, "work", now(), "work machine");
+
+        let map_path = dir.path().join("authors.toml");
+        fs::write(&map_path, "Swifty = [\"swiftyos\", \"Work@Example.com\"]\n").unwrap();
+        let aliases = author_map::AuthorMap::load(&map_path).unwrap();
+
+        // Tracking any alias tracks the person under the canonical name
+        let authors = AuthorFilter::new(vec!["swiftyos".to_string()], Vec::new(), aliases);
+        let stats = collect_stats(&repo, &authors, &CollectOptions::default()).unwrap();
+        assert_eq!(stats.keys().collect::<Vec<_>>(), ["Swifty"]);
+        assert_eq!(stats["Swifty"].committed_additions, 7);
+        assert_eq!(stats["Swifty"].commits.len(), 3);
+
+        let changes = changes_from_stats("repo", "Swifty", None, &stats["Swifty"], Utc::now());
+        assert!(changes.iter().all(|change| change.author.as_deref() == Some("Swifty")));
+    }
+
+    #[test]
+    fn untracked_files_count_as_pending_unless_ignored() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, ".gitignore", "build/\n");
+        commit_all(&repo, "Other", now(), "initial");
+
+        write_file(&repo, "new/notes.txt", &lines(5));
+        write_file(&repo, "build/output.txt", &lines(50));
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.pending_additions, 5);
+        assert_eq!(stats.pending_deletions, 0);
+    }
+
+    #[test]
+    fn repository_without_commits_still_reports_pending_changes() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "staged.txt", &lines(4));
+        write_file(&repo, "untracked.txt", &lines(2));
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.pending_additions, 6);
+        assert_eq!(stats.committed_additions, 0);
+
+        let changes = changes_from_stats("fresh", "Me", None, stats, Utc::now());
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].is_committed);
+        assert_eq!(changes[0].additions, 6);
+    }
+
+    #[test]
+    fn interval_must_be_at_least_one_second() {
+        assert_eq!(parse_interval_secs("10"), Ok(10));
+        assert!(parse_interval_secs("0").is_err());
+        assert!(parse_interval_secs("soon").is_err());
+    }
+
+    #[tokio::test]
+    async fn merge_commits_are_skipped_unless_requested() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "base.txt", &lines(1));
+        let base = commit_with_parents(&repo, "Other", now(), "base", &[]);
+
+        write_file(&repo, "feature.txt", &lines(8));
+        let feature = commit_with_parents(&repo, "Other", now(), "feature", &[base]);
+
+        fs::remove_file(repo.workdir().unwrap().join("feature.txt")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("feature.txt")).unwrap();
+        index.write().unwrap();
+        write_file(&repo, "main.txt", &lines(2));
+        let main = commit_with_parents(&repo, "Me", now(), "main", &[base]);
+
+        write_file(&repo, "feature.txt", &lines(8));
+        commit_with_parents(&repo, "Me", now(), "merge feature", &[main, feature]);
+
+        let skipped = collect_stats(&repo, &me(), &CollectOptions::default()).unwrap();
+        assert_eq!(skipped["Me"].committed_additions, 2);
+
+        let options = CollectOptions { count_merges: true, ..Default::default() };
+        let counted = collect_stats(&repo, &me(), &options).unwrap();
+        assert_eq!(counted["Me"].committed_additions, 10);
+
+        // The rows of the merge say it was counted against the first parent
+        let store = store::SqliteStore::memory().await;
+        store.store_changes(&changes_from_stats("repo", "Me", None, &counted["Me"], Utc::now())).await.unwrap();
+        let mut committed: Vec<(i32, bool)> = store
+            .query_range(&ChangeFilter::default())
+            .await
+            .unwrap()
+            .iter()
+            .filter(|change| change.is_committed)
+            .map(|change| (change.additions, change.is_merge))
+            .collect();
+        committed.sort();
+        assert_eq!(committed, [(2, false), (8, true)]);
+    }
+
+    #[test]
+    fn language_breakdown_buckets_changes_by_extension() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.rs", &lines(3));
+        write_file(&repo, "b.md", &lines(2));
+        commit_all(&repo, "Me", now(), "code and docs");
+
+        let options = CollectOptions { by_language: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.extensions.len(), 2);
+        assert_eq!(stats.extensions["rs"].committed_additions, 3);
+        assert_eq!(stats.extensions["md"].committed_additions, 2);
+        assert_eq!(stats.committed_additions, 5);
+
+        // The pending rows of the repository and each extension, then the same for the commit
+        let changes = changes_from_stats("repo", "Me", None, stats, Utc::now());
+        assert_eq!(changes.len(), 6);
+        assert_eq!(changes[1].extension.as_deref(), Some("md"));
+        assert!(!changes[1].is_committed);
+        assert_eq!(changes[4].extension.as_deref(), Some("md"));
+        assert_eq!((changes[4].is_committed, changes[4].additions), (true, 2));
+    }
+
+    #[test]
+    fn code_and_docs_are_counted_in_buckets_of_their_own() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "src/main.rs", &lines(4));
+        write_file(&repo, "GUIDE.md", &lines(3));
+        commit_all(&repo, "Me", now(), "code and docs");
+        write_file(&repo, "NOTES.TXT", &lines(2));
+
+        let options = CollectOptions { by_category: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        let code = &stats.categories[CODE_CATEGORY];
+        let docs = &stats.categories[DOCS_CATEGORY];
+        assert_eq!((code.committed_additions, code.committed_files, code.pending_additions), (4, 1, 0));
+        assert_eq!((docs.committed_additions, docs.committed_files), (3, 1));
+        assert_eq!((docs.pending_additions, docs.pending_files), (2, 1));
+        assert_eq!((stats.committed_additions, stats.pending_additions), (7, 2));
+
+        // Each bucket gets a row of its own, which the totals leave out
+        let changes = changes_from_stats("repo", "Me", None, stats, Utc::now());
+        let row = |committed: bool, category: &str| {
+            let found = changes.iter().find(|change| {
+                change.is_committed == committed && change.category.as_deref() == Some(category)
+            });
+            found.map(|change| change.additions)
+        };
+        assert_eq!((row(true, "code"), row(true, "docs"), row(false, "docs")), (Some(4), Some(3), Some(2)));
+        let totals = report::daily_totals(&changes);
+        assert!(totals.iter().all(|change| change.is_total()));
+        assert_eq!(totals.iter().find(|change| change.is_committed).unwrap().additions, 7);
+
+        // Other extensions can be counted as docs instead
+        let options = CollectOptions { by_category: true, doc_extensions: vec!["rs".to_string()], ..options };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.categories[DOCS_CATEGORY].committed_additions, 4);
+        assert_eq!(stats.categories[CODE_CATEGORY].committed_additions, 3);
+    }
+
+    #[tokio::test]
+    async fn identical_consecutive_polls_are_stored_once() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(2));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Me", now(), "more");
+        write_file(&repo, "a.txt", &lines(6));
+
+        let store = store::SqliteStore::memory().await;
+        let paths = vec![dir.path().to_path_buf()];
+        let options = CollectOptions::default();
+        let count_rows = || async {
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM loc_changes")
+                .fetch_one(&store.pool())
+                .await
+                .unwrap();
+            count
+        };
+
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert_eq!(count_rows().await, 2);
+
+        // Only the pending snapshot is new, the commit is not stored again
+        write_file(&repo, "a.txt", &lines(8));
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert_eq!(count_rows().await, 3);
+
+        poll_repositories(&store, &paths, &me(), &options, true, &mut state).await;
+        assert_eq!(count_rows().await, 4);
+    }
+
+    #[tokio::test]
+    async fn a_subdirectory_is_polled_as_its_repository() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "src/lib/a.txt", &lines(2));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "README", &lines(3));
+
+        let nested = dir.path().join("src/lib");
+        let root_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+        let (_, repo_name) = open_repository(&nested).unwrap();
+        assert_eq!(repo_name, root_name);
+
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState::default();
+        poll_repositories(&store, &[nested], &me(), &CollectOptions::default(), false, &mut state).await;
+        // Work outside the subdirectory still counts, it is the same repository
+        let totals = state.repo_totals();
+        assert_eq!(totals.keys().collect::<Vec<_>>(), [&root_name]);
+        assert_eq!(totals[&root_name].pending_additions, 3);
+    }
+
+    #[test]
+    fn only_events_that_can_change_the_counts_trigger_a_poll() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, ".gitignore", "target/\n");
+        write_file(&repo, "src.rs", &lines(1));
+        commit_all(&repo, "Me", now(), "initial");
+        let paths = vec![dir.path().to_path_buf()];
+        let git_dir = repo.path().to_path_buf();
+        let triggers = |event: DebouncedEvent| triggers_poll(&event, &paths);
+
+        // Build output and git's object store do not change what is counted
+        assert!(!triggers(DebouncedEvent::Write(dir.path().join("target/debug/app"))));
+        assert!(!triggers(DebouncedEvent::Create(git_dir.join("objects/ab/cdef"))));
+        assert!(!triggers(DebouncedEvent::NoticeWrite(dir.path().join("src.rs"))));
+
+        assert!(triggers(DebouncedEvent::Write(dir.path().join("src.rs"))));
+        assert!(triggers(DebouncedEvent::Write(git_dir.join("refs/heads/master"))));
+        assert!(triggers(DebouncedEvent::Rename(git_dir.join("index.lock"), git_dir.join("index"))));
+        assert!(triggers(DebouncedEvent::Rescan));
+    }
+
+    #[tokio::test]
+    async fn a_burst_of_events_is_counted_by_a_single_poll() {
+        let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let saved = PathBuf::from("/work/api/src/lib.rs");
+        for _ in 0..20 {
+            tx.send(DebouncedEvent::Write(saved.clone())).unwrap();
+        }
+        tx.send(DebouncedEvent::Error(notify::Error::Generic("overflow".to_string()), None)).unwrap();
+
+        // The loop wakes on the first event and polls once for all of them
+        let mut polls = 0;
+        while let Ok(first) = events.try_recv() {
+            assert_eq!(first, DebouncedEvent::Write(saved.clone()));
+            assert_eq!(drain_pending_events(&mut events), 20);
+            polls += 1;
+        }
+        assert_eq!(polls, 1);
+
+        // Events arriving after the poll trigger the next one
+        tx.send(DebouncedEvent::Write(saved.clone())).unwrap();
+        assert!(events.recv().await.is_some());
+        assert_eq!(drain_pending_events(&mut events), 0);
+    }
+
+    #[tokio::test]
+    async fn each_author_gets_a_line_per_repository() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "mine");
+        write_file(&repo, "a.txt", &lines(6));
+        commit_all(&repo, "Teammate", now(), "theirs");
+        let repo_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+
+        let authors = AuthorFilter::new(vec!["Me".to_string(), "Teammate".to_string()], Vec::new(), Default::default());
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState::default();
+        poll_repositories(&store, &[dir.path().to_path_buf()], &authors, &CollectOptions::default(), false, &mut state)
+            .await;
+        assert_eq!(state.repo_stats[&(repo_name.clone(), "Me".to_string())].committed_additions, 2);
+        assert_eq!(state.repo_stats[&(repo_name.clone(), "Teammate".to_string())].committed_additions, 3);
+
+        let display = DisplayOptions { by_author: true, ..Default::default() };
+        let output = status::render(&state.repo_stats, &display, Utc::now(), 0, None);
+        let lines: Vec<&str> = output.lines().collect();
+        let line = |label: &str, committed: i32| {
+            let width = (repo_name.len() + 1).max("  Teammate:".len());
+            format!("{:<width$} {} LoC committed, 0 LoC In Progress", label, committed, width = width)
+        };
+        assert_eq!(lines[0], line(&format!("{}:", repo_name), 5));
+        assert_eq!(lines[1], line("  Me:", 2));
+        assert_eq!(lines[2], line("  Teammate:", 3));
+        assert_eq!(lines[4], line("Total:", 5));
+        assert_eq!(lines[5], line("  Me:", 2));
+        assert_eq!(lines[6], line("  Teammate:", 3));
+    }
+
+    #[tokio::test]
+    async fn large_commits_are_flagged_and_left_out_of_the_goal() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "vendor.txt", &lines(5000));
+        let vendored = commit_all(&repo, "Me", now(), "vendor everything");
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Me", now(), "mine");
+        let repo_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+
+        let options = CollectOptions { flag_large_commit: Some(1000), ..Default::default() };
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState::default();
+        poll_repositories(&store, &[dir.path().to_path_buf()], &me(), &options, false, &mut state).await;
+        assert_eq!(state.repo_stats[&(repo_name.clone(), "Me".to_string())].committed_additions, 3);
+        assert!(state.flagged_commits.contains(&vendored.to_string()));
+
+        let display = DisplayOptions { daily_goal: Some(100), ..Default::default() };
+        assert!(status::render(&state.repo_stats, &display, Utc::now(), 0, None).contains(" 3/100 LoC"));
+
+        // The commit is kept, flagged, but totals read back from the store skip it too
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let stored = changes.iter().find(|change| change.commit_sha == Some(vendored.to_string())).unwrap();
+        assert!(stored.flagged && stored.additions == 5000);
+        let today = report::load_latest_stats(&store, Local::now().date_naive(), 0).await.unwrap();
+        assert_eq!(today[&(repo_name, "Me".to_string())].committed_additions, 3);
+    }
+
+    #[tokio::test]
+    async fn each_run_tags_its_rows_with_its_own_session() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "mine");
+        let paths = vec![dir.path().to_path_buf()];
+        let store = store::SqliteStore::memory().await;
+
+        // A restart writes the same stats again, told apart only by the session
+        let sessions = [new_session_id(), new_session_id()];
+        assert_ne!(sessions[0], sessions[1]);
+        for session_id in &sessions {
+            let mut state = WatchState { session_id: Some(session_id.clone()), ..Default::default() };
+            poll_repositories(&store, &paths, &me(), &CollectOptions::default(), true, &mut state).await;
+        }
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let stored: BTreeSet<Option<String>> = changes.iter().map(|change| change.session_id.clone()).collect();
+        assert_eq!(stored, sessions.iter().cloned().map(Some).collect());
+
+        let opt = WatchOpt::from_iter(["watch", "--author", "Me", "--session-id", "test-run"]);
+        assert_eq!(opt.session_id.as_deref(), Some("test-run"));
+    }
+
+    #[tokio::test]
+    async fn changes_kept_while_the_database_is_busy_are_written_before_exiting() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "initial");
+        let paths = vec![dir.path().to_path_buf()];
+        let db_dir = TempDir::new().unwrap();
+        let db_path = db_dir.path().join("stats.db");
+        let store = store::SqliteStore::open(&db_path).await.unwrap();
+        store.setup().await.unwrap();
+        let impatient = store::SqliteStore::impatient(&db_path).await;
+
+        let mut lock = store.pool().acquire().await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE").execute(&mut *lock).await.unwrap();
+        let mut state = WatchState::default();
+        poll_repositories(&impatient, &paths, &me(), &CollectOptions::default(), true, &mut state).await;
+        let kept = state.unsaved.len();
+        assert!(kept > 0);
+
+        // Still busy after the last retries, so exiting reports the changes lost
+        let error = state.flush_before_exit(&impatient).await.unwrap_err();
+        assert!(error.contains(&format!("{} change(s)", kept)), "{}", error);
+
+        sqlx::query("COMMIT").execute(&mut *lock).await.unwrap();
+        state.flush_before_exit(&impatient).await.unwrap();
+        assert!(state.unsaved.is_empty());
+        assert_eq!(store.query_range(&ChangeFilter::default()).await.unwrap().len(), kept);
+    }
+
+    #[test]
+    fn authors_are_listed_by_their_number_of_commits() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Teammate", now(), "initial");
+        write_file(&repo, "a.txt", &lines(2));
+        commit_all(&repo, "Me", now(), "first");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "second");
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Stranger", Time::new(now().seconds() - 100 * 86_400, 0), "long ago");
+
+        let count = |name: &str, commits| authors::AuthorCount {
+            name: name.to_string(),
+            email: format!("{}@example.com", name),
+            commits,
+        };
+        assert_eq!(authors::count_authors(&repo, 90).unwrap(), [count("Me", 2), count("Teammate", 1)]);
+        assert_eq!(authors::count_authors(&repo, 365).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn fetched_commits_on_the_remote_branch_are_counted() {
+        let (remote_dir, remote) = init_repo();
+        write_file(&remote, "a.txt", &lines(1));
+        commit_all(&remote, "Other", now(), "initial");
+        let (dir, _) = init_repo();
+        let local = Repository::clone(remote_dir.path().to_str().unwrap(), dir.path().join("clone")).unwrap();
+
+        write_file(&remote, "a.txt", &lines(5));
+        commit_all(&remote, "Teammate", now(), "reviewed work");
+        let branch = remote.head().unwrap().shorthand().unwrap().to_string();
+        let options = CollectOptions { remote_branch: Some(format!("origin/{}", branch)), ..Default::default() };
+        let teammate = AuthorFilter::new(vec!["Teammate".to_string()], Vec::new(), Default::default());
+        let committed = || collect_stats(&local, &teammate, &options).unwrap()["Teammate"].committed_additions;
+
+        assert_eq!(committed(), 0);
+        fetch::fetch(&local, fetch::remote_name(options.remote_branch.as_deref())).unwrap();
+        assert_eq!(committed(), 4);
+        // The checked out branch is left as it was
+        let head = local.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("initial"));
+    }
+
+    #[tokio::test]
+    async fn without_an_author_the_user_of_the_git_config_is_tracked() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "mine");
+        write_file(&repo, "a.txt", &lines(6));
+        commit_all(&repo, "Other", now(), "theirs");
+        repo.config().unwrap().set_str("user.name", "Me").unwrap();
+        let repo_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+        let paths = vec![dir.path().to_path_buf()];
+
+        let authors = AuthorFilter::default();
+        assert!(tracked_identities(&paths, &authors).contains("Me"));
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &authors, &CollectOptions::default(), false, &mut state).await;
+        assert_eq!(state.repo_stats[&(repo_name.clone(), "Me".to_string())].committed_additions, 2);
+        assert!(!state.repo_stats.contains_key(&(repo_name, "Other".to_string())));
+    }
+
+    #[tokio::test]
+    async fn without_an_author_or_a_git_user_pending_changes_are_still_counted() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(4));
+        // Empty values hide any user of the global git config
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "").unwrap();
+        config.set_str("user.email", "").unwrap();
+        let repo_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+        let paths = vec![dir.path().to_path_buf()];
+
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &AuthorFilter::default(), &CollectOptions::default(), false, &mut state)
+            .await;
+        let stats = &state.repo_stats[&(repo_name, UNKNOWN_AUTHOR.to_string())];
+        assert_eq!((stats.pending_additions, stats.committed_additions), (3, 0));
+        assert!(stats.commits.is_empty());
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].author.as_deref(), changes[0].is_committed), (Some(UNKNOWN_AUTHOR), false));
+        assert_eq!(changes[0].author_email, None);
+    }
+
+    #[tokio::test]
+    async fn an_author_matching_none_of_todays_commits_is_warned_about() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", days_ago(1), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Mee", now(), "typo in the config");
+        write_file(&repo, "a.txt", &lines(4));
+        commit_all(&repo, "Teammate", now(), "more");
+
+        let options = CollectOptions::default();
+        let names: Vec<String> = todays_authors(&repo, &options).unwrap().into_iter().collect();
+        assert_eq!(names, ["Mee", "Teammate"]);
+
+        let store = store::SqliteStore::memory().await;
+        let paths = [dir.path().to_path_buf()];
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert!(state.warned_unmatched);
+
+        let mine = AuthorFilter::new(vec!["Mee".to_string()], Vec::new(), Default::default());
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &mine, &options, false, &mut state).await;
+        assert!(!state.warned_unmatched);
+    }
+
+    #[tokio::test]
+    async fn a_dry_run_never_touches_the_database() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(2));
+        let db_dir = TempDir::new().unwrap();
+        let db_path = db_dir.path().join("stats.db");
+
+        let opt = WatchOpt::from_iter([
+            "watch".as_ref(),
+            dir.path().as_os_str(),
+            "-a".as_ref(),
+            "Me".as_ref(),
+            "--once".as_ref(),
+            "--quiet".as_ref(),
+            "--dry-run".as_ref(),
+            "--db-path".as_ref(),
+            db_path.as_os_str(),
+        ]);
+        watch_repositories(opt).await.unwrap();
+        assert!(!db_path.exists());
+    }
+
+    #[tokio::test]
+    async fn a_removed_repository_is_dropped_until_it_is_back() {
+        let (kept_dir, kept) = init_repo();
+        write_file(&kept, "a.txt", &lines(2));
+        let (removed_dir, removed) = init_repo();
+        write_file(&removed, "b.txt", &lines(3));
+        let removed_path = removed_dir.path().to_path_buf();
+        let name = |dir: &TempDir| dir.path().file_name().unwrap().to_string_lossy().into_owned();
+
+        let store = store::SqliteStore::memory().await;
+        let paths = vec![kept_dir.path().to_path_buf(), removed_path.clone()];
+        let options = CollectOptions::default();
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert_eq!(state.repo_stats.len(), 2);
+
+        fs::remove_dir_all(&removed_path).unwrap();
+        for _ in 0..2 {
+            poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+            assert_eq!(state.repo_totals().keys().collect::<Vec<_>>(), [&name(&kept_dir)]);
+            assert!(state.missing.contains(&removed_path));
+        }
+
+        // The other repositories keep being polled
+        write_file(&kept, "a.txt", &lines(5));
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert_eq!(state.repo_totals()[&name(&kept_dir)].pending_additions, 5);
+
+        let back = Repository::init(&removed_path).unwrap();
+        write_file(&back, "c.txt", &lines(1));
+        poll_repositories(&store, &paths, &me(), &options, false, &mut state).await;
+        assert!(state.missing.is_empty());
+        assert_eq!(state.repo_totals()[&name(&removed_dir)].pending_additions, 1);
+    }
+
+    #[test]
+    fn invalid_paths_fail_validation_unless_skipped() {
+        let (dir, _repo) = init_repo();
+        let not_a_repo = TempDir::new().unwrap();
+        let paths = vec![dir.path().to_path_buf(), not_a_repo.path().to_path_buf()];
+
+        assert!(validate_repositories(paths.clone(), false, false).is_err());
+        assert_eq!(validate_repositories(paths, true, false).unwrap(), [dir.path().to_path_buf()]);
+        assert!(validate_repositories(vec![not_a_repo.path().to_path_buf()], true, false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlinked_repository_is_tracked_under_its_real_name() {
+        let (dir, _repo) = init_repo();
+        let links = TempDir::new().unwrap();
+        let link = links.path().join("shortcut");
+        std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+        let real = std::fs::canonicalize(dir.path()).unwrap();
+
+        // Naming the same repository twice keeps one of them
+        let paths = validate_repositories(vec![link.clone(), dir.path().to_path_buf()], false, true).unwrap();
+        assert_eq!(paths, [real.as_path()]);
+        let (_, repo_name) = open_repository(&paths[0]).unwrap();
+        assert_eq!(repo_name, real.file_name().unwrap().to_string_lossy());
+
+        let as_given = validate_repositories(vec![link.clone()], false, false).unwrap();
+        assert_eq!(as_given, [link]);
+    }
+
+    #[tokio::test]
+    async fn repositories_sharing_a_directory_name_are_kept_apart() {
+        let orgs = TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for org in ["org-a", "org-b", "org-c"] {
+            let path = orgs.path().join(org).join("api");
+            let repo = Repository::init(&path).unwrap();
+            write_file(&repo, "a.txt", &lines(1));
+            commit_all(&repo, "Other", now(), "initial");
+            write_file(&repo, "a.txt", &lines(if org == "org-a" { 3 } else { 5 }));
+            paths.push(path);
+        }
+
+        // One is named explicitly, the other two by their parent directory
+        let named = vec![("work-api".to_string(), paths[2].clone())];
+        let repo_names = repository_names(&paths, &named);
+        assert_eq!(repo_names[&paths[0]], "org-a/api");
+        assert_eq!(repo_names[&paths[1]], "org-b/api");
+        assert_eq!(repo_names[&paths[2]], "work-api");
+        // Alone, a repository keeps its directory name
+        assert_eq!(repository_names(&paths[..1], &[])[&paths[0]], "api");
+
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState { repo_names, ..Default::default() };
+        poll_repositories(&store, &paths, &me(), &CollectOptions::default(), false, &mut state).await;
+        let totals = state.repo_totals();
+        assert_eq!(totals.keys().collect::<Vec<_>>(), ["org-a/api", "org-b/api", "work-api"]);
+        assert_eq!(totals["org-a/api"].pending_additions, 2);
+        assert_eq!(totals["org-b/api"].pending_additions, 4);
+
+        let stored = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let stored_names: BTreeSet<&str> = stored.iter().map(|change| change.repo_name.as_str()).collect();
+        assert_eq!(stored_names.into_iter().collect::<Vec<_>>(), ["org-a/api", "org-b/api", "work-api"]);
+
+        assert_eq!(parse_named_repo("web=/src/web"), Ok(("web".to_string(), PathBuf::from("/src/web"))));
+        assert!(parse_named_repo("/src/web").is_err());
+        assert!(parse_named_repo("=/src/web").is_err());
+    }
+
+    #[test]
+    fn current_branch_names_the_branch_or_the_detached_commit() {
+        let (_dir, repo) = init_repo();
+        assert_eq!(current_branch(&repo).as_deref(), Some("master"));
+
+        write_file(&repo, "a.txt", &lines(1));
+        let oid = commit_all(&repo, "Me", now(), "initial");
+        let feature = repo.branch("feature", &repo.find_commit(oid).unwrap(), false).unwrap();
+        repo.set_head(feature.get().name().unwrap()).unwrap();
+        assert_eq!(current_branch(&repo).as_deref(), Some("feature"));
+
+        repo.set_head_detached(oid).unwrap();
+        let short_sha = current_branch(&repo).unwrap();
+        assert!(oid.to_string().starts_with(&short_sha));
+    }
+
+    #[test]
+    fn renamed_files_count_only_their_edits() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "old_name.txt", &lines(100));
+        commit_all(&repo, "Other", now(), "initial");
+
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let mut contents = fs::read_to_string(workdir.join("old_name.txt")).unwrap();
+        contents = contents.replace("line 10\n", "edited 10\n").replace("line 20\n", "edited 20\n");
+        fs::remove_file(workdir.join("old_name.txt")).unwrap();
+        write_file(&repo, "new_name.txt", &contents);
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old_name.txt")).unwrap();
+        index.write().unwrap();
+        commit_all(&repo, "Me", now(), "rename");
+
+        let stats = &collect_stats(&repo, &me(), &CollectOptions::default()).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 2);
+        assert_eq!(stats.committed_deletions, 2);
+
+        // The commit row keeps the file's counts under its new path, remembering the old one
+        let changes = changes_from_stats("api", "Me", None, stats, Utc::now());
+        let file = FileChange {
+            path: "new_name.txt".to_string(),
+            old_path: Some("old_name.txt".to_string()),
+            additions: 2,
+            deletions: 2,
+        };
+        assert_eq!(changes[1].files, [file]);
+    }
+
+    #[test]
+    fn a_huge_commit_keeps_counts_of_a_bounded_number_of_files() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+
+        // A vendored drop: more files than get counts of their own, one of them large
+        for i in 0..MAX_FILE_COUNTS + 5 {
+            write_file(&repo, &format!("vendor/{:04}.txt", i), &lines(1));
+        }
+        write_file(&repo, "vendor/big.txt", &lines(200_000));
+        commit_all(&repo, "Me", now(), "vendor");
+
+        let options = CollectOptions { by_language: true, ..Default::default() };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 200_000 + MAX_FILE_COUNTS as i32 + 5);
+        assert_eq!(stats.committed_files, MAX_FILE_COUNTS as i32 + 6);
+        assert_eq!(stats.extensions["txt"].committed_additions, stats.committed_additions);
+        assert_eq!(stats.commits[0].files.len(), MAX_FILE_COUNTS);
+    }
+
+    #[test]
+    fn excluded_paths_are_left_out_of_all_counts() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "src/main.rs", &lines(3));
+        write_file(&repo, "Cargo.lock", &lines(40));
+        write_file(&repo, "vendor/lib/dep.rs", &lines(50));
+        commit_all(&repo, "Me", now(), "add code and vendored deps");
+        write_file(&repo, "src/main.rs", &lines(5));
+        write_file(&repo, "Cargo.lock", &lines(60));
+        write_file(&repo, "vendor/lib/other.rs", &lines(70));
+
+        let options = CollectOptions {
+            exclude: vec!["vendor/**".to_string(), "*.lock".to_string()],
+            ..Default::default()
+        };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 3);
+        assert_eq!(stats.pending_additions, 2);
+    }
+
+    #[tokio::test]
+    async fn backfill_buckets_past_commits_by_day_once() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", days_ago(30), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", days_ago(20), "too old");
+        write_file(&repo, "a.txt", &lines(6));
+        commit_all(&repo, "Me", days_ago(3), "three days ago");
+        write_file(&repo, "b.txt", &lines(4));
+        commit_all(&repo, "Other", days_ago(2), "someone else");
+        write_file(&repo, "a.txt", &lines(7));
+        commit_all(&repo, "Me", days_ago(1), "yesterday");
+        write_file(&repo, "c.txt", &lines(2));
+        commit_all(&repo, "Me", days_ago(1), "yesterday again");
+        write_file(&repo, "a.txt", &lines(50));
+        commit_all(&repo, "Me", now(), "today");
+
+        let today = Local::now().date_naive();
+        let history =
+            backfill::collect_history(&repo, &me(), &CollectOptions::default(), today - chrono::Duration::days(7), today)
+                .unwrap();
+        let days: Vec<_> = history.keys().map(|(date, author)| ((today - *date).num_days(), author.as_str())).collect();
+        assert_eq!(days, [(3, "Me"), (1, "Me")]);
+        let yesterday = &history[&(today - chrono::Duration::days(1), "Me".to_string())];
+        assert_eq!(yesterday.committed_additions, 3);
+        assert_eq!(yesterday.commits.len(), 2);
+
+        let store = store::SqliteStore::memory().await;
+        assert_eq!(backfill::store_history(&store, "repo", Some("master"), &history, "backfill", 0).await.unwrap(), 3);
+        assert_eq!(backfill::store_history(&store, "repo", Some("master"), &history, "backfill", 0).await.unwrap(), 0);
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().all(|change| change.is_committed && change.commit_sha.is_some()));
+        assert_eq!(changes[0].additions, 3);
+    }
+
+    #[tokio::test]
+    async fn catching_up_stores_the_commits_made_while_not_watching() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", days_ago(10), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", days_ago(3), "before the last run");
+
+        // The last run stored its final poll two days ago
+        let store = store::SqliteStore::memory().await;
+        let last_poll = DateTime::from_timestamp(days_ago(2).seconds(), 0).unwrap();
+        let stats = RepoStats { pending_additions: 1, ..Default::default() };
+        store.store_changes(&changes_from_stats("repo", "Me", None, &stats, last_poll)).await.unwrap();
+
+        let mut gap = Vec::new();
+        let an_hour_later = Time::new(days_ago(1).seconds() + 3_600, days_ago(1).offset_minutes());
+        for (contents, time) in [(6, days_ago(1)), (8, an_hour_later), (9, now())] {
+            write_file(&repo, "a.txt", &lines(contents));
+            commit_all(&repo, "Me", time, "while not watching");
+            gap.push(DateTime::from_timestamp(time.seconds(), 0).unwrap());
+        }
+
+        let paths = vec![dir.path().to_path_buf()];
+        let (repo_names, authors, options) = (repository_names(&paths, &[]), me(), CollectOptions::default());
+        let caught_up = backfill::catch_up(&store, &paths, &repo_names, &authors, &options, "run").await.unwrap();
+        assert_eq!(caught_up, 3);
+
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let commits: Vec<(DateTime<Utc>, i32)> = changes
+            .iter()
+            .filter(|change| change.is_committed)
+            .map(|change| (change.timestamp, change.additions))
+            .collect();
+        assert_eq!(commits, [(gap[0], 3), (gap[1], 2), (gap[2], 1)]);
+
+        // Running again finds nothing new
+        assert_eq!(backfill::catch_up(&store, &paths, &repo_names, &authors, &options, "run").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn backfill_counts_the_first_commit_as_additions() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(5));
+        write_file(&repo, "b.txt", &lines(2));
+        commit_all(&repo, "Me", days_ago(2), "initial");
+
+        // Tracking today is unaffected: a root commit has nothing to diff against
+        let today = Local::now().date_naive();
+        let history =
+            backfill::collect_history(&repo, &me(), &CollectOptions::default(), today - chrono::Duration::days(7), today)
+                .unwrap();
+        assert!(history.is_empty());
+
+        let db_dir = TempDir::new().unwrap();
+        let db_path = db_dir.path().join("stats.db");
+        let opt = BackfillOpt::from_iter([
+            "backfill".as_ref(),
+            dir.path().as_os_str(),
+            "-a".as_ref(),
+            "Me".as_ref(),
+            "--db-path".as_ref(),
+            db_path.as_os_str(),
+        ]);
+        backfill::run(opt).await.unwrap();
+
+        let store = store::SqliteStore::open(&db_path).await.unwrap();
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].additions, changes[0].deletions, changes[0].files_changed), (7, 0, 2));
+    }
+
+    #[tokio::test]
+    async fn a_deleted_database_is_recreated_by_the_next_poll() {
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+
+        let db_dir = TempDir::new().unwrap();
+        let db_path = db_dir.path().join("stats.db");
+        let store = store::SqliteStore::open(&db_path).await.unwrap();
+        store.enable_wal().await.unwrap();
+        store.setup().await.unwrap();
+        let paths = [dir.path().to_path_buf()];
+        let mut state = WatchState::default();
+        poll_repositories(&store, &paths, &me(), &CollectOptions::default(), false, &mut state).await;
+
+        // Cleaned up along with the rest of its directory
+        fs::remove_dir_all(db_dir.path()).unwrap();
+        write_file(&repo, "a.txt", &lines(6));
+        poll_repositories(&store, &paths, &me(), &CollectOptions::default(), false, &mut state).await;
+
+        assert!(db_path.exists());
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].additions, 5);
+        store.close().await;
+    }
+
+    #[test]
+    fn commits_matching_the_skip_pattern_are_not_counted() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(3));
+        commit_all(&repo, "Me", now(), "feat: x");
+        write_file(&repo, "a.txt", &lines(10));
+        commit_all(&repo, "Me", now(), "wip: y");
+
+        let options = CollectOptions {
+            skip_message: Some(Regex::new("^wip").unwrap()),
+            ..Default::default()
+        };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 2);
+    }
+
+    #[test]
+    fn commits_are_dated_in_their_own_offset_unless_overridden() {
+        // 23:30 on March 12th in UTC-8 is already March 13th in UTC
+        let late_evening = Time::new(1_710_315_000, -8 * 60);
+        let march = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        assert_eq!(commit_date(&late_evening, None, 0), Some(march(12)));
+        assert_eq!(commit_date(&late_evening, Some(parse_timezone("UTC").unwrap()), 0), Some(march(13)));
+        assert_eq!(commit_date(&late_evening, Some(parse_timezone("+09:00").unwrap()), 0), Some(march(13)));
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn commits_before_the_day_start_hour_count_for_the_day_before() {
+        // 01:00 on March 13th in UTC+2
+        let after_midnight = Time::new(1_710_284_400, 2 * 60);
+        let march = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        let opt = WatchOpt::from_iter(["dev_metrics", "--author", "Me", "--day-start-hour", "4"]);
+        let options = opt.collect_options();
+        assert_eq!(options.day_start_hour, 4);
+        assert_eq!(commit_date(&after_midnight, options.timezone, options.day_start_hour), Some(march(12)));
+        assert_eq!(commit_date(&after_midnight, None, 0), Some(march(13)));
+        // The day still starts at the hour in the offset given with --timezone
+        assert_eq!(commit_date(&after_midnight, Some(parse_timezone("+05:00").unwrap()), 4), Some(march(13)));
+        assert!(parse_day_start_hour("24").is_err());
+    }
+
+    #[tokio::test]
+    async fn a_commit_before_the_day_start_hour_counts_for_the_same_day_everywhere() {
+        // Days start an hour from now, so a commit made now belongs to the day before, unless that hour is midnight
+        let day_start_hour = (chrono::Timelike::hour(&Local::now()) + 1) % 24;
+        let (dir, repo) = init_repo();
+        write_file(&repo, "a.txt", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "a.txt", &lines(5));
+        commit_all(&repo, "Me", now(), "late night");
+        let key = (dir.path().file_name().unwrap().to_string_lossy().into_owned(), "Me".to_string());
+
+        let options = CollectOptions { day_start_hour, ..Default::default() };
+        let store = store::SqliteStore::memory().await;
+        let mut state = WatchState { day_start_hour, ..Default::default() };
+        poll_repositories(&store, &[dir.path().to_path_buf()], &me(), &options, false, &mut state).await;
+        let today = state.today();
+        if day_start_hour > 0 {
+            assert_eq!(today, Local::now().date_naive() - chrono::Duration::days(1));
+        }
+        assert_eq!(state.repo_stats[&key].committed_additions, 4);
+        assert!(state.active_days.contains(&today));
+        assert_eq!(state.streak(), 1);
+
+        // A restart restores the same day and streak the status line showed
+        let restored = report::load_latest_stats(&store, today, day_start_hour).await.unwrap();
+        assert_eq!(restored[&key].committed_additions, 4);
+        let active_days = streak::active_days(&store, std::iter::once(&key.1), day_start_hour).await.unwrap();
+        assert_eq!(active_days, state.active_days);
+
+        // The day's rollup takes the commit in, and pruning counts from the same start
+        let summaries = rollup::rollup_day(&store, today, day_start_hour).await.unwrap();
+        assert_eq!(summaries[0].committed_additions, 4);
+        assert_eq!(rollup::prune_old_changes(&store, 0, false, today, day_start_hour).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn subpaths_limit_the_counts_to_their_tree() {
+        let (_dir, repo) = init_repo();
+        write_file(&repo, "README", &lines(1));
+        commit_all(&repo, "Other", now(), "initial");
+        write_file(&repo, "src/lib.rs", &lines(4));
+        write_file(&repo, "src/deps.lock", &lines(30));
+        write_file(&repo, "docs/guide.md", &lines(40));
+        write_file(&repo, "srcs.txt", &lines(50));
+        commit_all(&repo, "Me", now(), "code and docs");
+        write_file(&repo, "src/lib.rs", &lines(6));
+        write_file(&repo, "docs/guide.md", &lines(70));
+
+        let options = CollectOptions {
+            subpaths: vec!["src".to_string()],
+            exclude: vec!["*.lock".to_string()],
+            ..Default::default()
+        };
+        let stats = &collect_stats(&repo, &me(), &options).unwrap()["Me"];
+        assert_eq!(stats.committed_additions, 4);
+        assert_eq!(stats.pending_additions, 2);
+    }
+}
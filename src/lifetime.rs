@@ -0,0 +1,133 @@
+//! Committed LoC per repository over everything stored.
+
+use std::collections::BTreeMap;
+
+use crate::report::{align_table, daily_totals};
+use crate::{store, ChangeFilter, LifetimeOpt, LocChange, RepoStats};
+
+/// Runs the `lifetime` subcommand, printing the committed LoC of every repository.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed lifetime options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: LifetimeOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = ChangeFilter { author: opt.author, ..Default::default() };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let changes = store.query_range(&filter).await?;
+    store.close().await;
+
+    print!("{}", format_lifetime(&lifetime_totals(&changes)));
+    Ok(())
+}
+
+/// Sums the committed changes of every stored day per repository.
+///
+/// Each commit is stored once, and of the older snapshot rows only the last of
+/// each day counts, so no change is counted twice.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to sum.
+///
+/// # Returns
+///
+/// A map from repository name to its committed totals, ordered by name.
+pub fn lifetime_totals(changes: &[LocChange]) -> BTreeMap<String, RepoStats> {
+    let mut totals: BTreeMap<String, RepoStats> = BTreeMap::new();
+    for change in daily_totals(changes).into_iter().filter(|change| change.is_committed) {
+        let stats = totals.entry(change.repo_name).or_default();
+        stats.committed_additions += change.additions;
+        stats.committed_deletions += change.deletions;
+    }
+
+    totals
+}
+
+/// Formats the lifetime totals as an aligned table with a grand total row.
+///
+/// # Arguments
+///
+/// * `totals` - The committed totals per repository.
+pub fn format_lifetime(totals: &BTreeMap<String, RepoStats>) -> String {
+    let mut grand_total = RepoStats::default();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (repo_name, stats) in totals {
+        grand_total += stats;
+        rows.push(lifetime_row(repo_name, stats));
+    }
+    rows.push(lifetime_row("Total", &grand_total));
+
+    align_table(&["Repository"], &["Added", "Deleted", "LoC"], &rows)
+}
+
+fn lifetime_row(label: &str, stats: &RepoStats) -> Vec<String> {
+    vec![
+        label.to_string(),
+        stats.committed_additions.to_string(),
+        stats.committed_deletions.to_string(),
+        (stats.committed_additions + stats.committed_deletions).to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::start_of_local_day;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn every_commit_counts_once() {
+        let day = start_of_local_day(NaiveDate::from_ymd_opt(2024, 3, 12).unwrap());
+        let change = |repo: &str, hours: i64, additions: i32, commit_sha: Option<&str>| LocChange {
+            repo_name: repo.to_string(),
+            timestamp: day + Duration::hours(hours),
+            author: Some("Me".to_string()),
+            author_email: None,
+            additions,
+            deletions: 1,
+            is_committed: true,
+            extension: None,
+            category: None,
+            branch: None,
+            commit_sha: commit_sha.map(str::to_string),
+            staged_additions: 0,
+            staged_deletions: 0,
+            files_changed: 0,
+            inserted_lines: 0,
+            edited_lines: 0,
+            flagged: false,
+            session_id: None,
+            is_merge: false,
+            files: Vec::new(),
+        };
+
+        let changes = [
+            // Commits on two days
+            change("api", 9, 10, Some("a1")),
+            change("api", 30, 5, Some("a2")),
+            // Snapshots of a running total, of which the day's last counts
+            change("web", 10, 4, None),
+            change("web", 12, 7, None),
+            change("web", 34, 2, None),
+            // Pending work and the extension breakdown are left out
+            LocChange { is_committed: false, ..change("api", 10, 99, None) },
+            LocChange { extension: Some("rs".to_string()), ..change("api", 9, 10, Some("a1")) },
+        ];
+
+        let totals = lifetime_totals(&changes);
+        assert_eq!((totals["api"].committed_additions, totals["api"].committed_deletions), (15, 2));
+        assert_eq!((totals["web"].committed_additions, totals["web"].committed_deletions), (9, 2));
+        assert_eq!(totals["api"].pending_additions, 0);
+
+        let table = format_lifetime(&totals);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "Repository  Added  Deleted  LoC");
+        assert_eq!(lines[3], "Total          24        4   28");
+    }
+}
@@ -0,0 +1,146 @@
+//! A Prometheus endpoint exposing the stats of the latest poll.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, RwLock};
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use tokio::net::TcpListener;
+
+use crate::RepoStats;
+
+/// The stats served on `/metrics`, shared between the watch loop and the server.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    repo_stats: Arc<RwLock<BTreeMap<String, RepoStats>>>,
+}
+
+impl Metrics {
+    /// Replaces the published stats with the ones of the latest poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `repo_stats` - The latest stats per repository.
+    pub fn update(&self, repo_stats: &BTreeMap<String, RepoStats>) {
+        *self.repo_stats.write().expect("metrics lock poisoned") = repo_stats.clone();
+    }
+
+    /// Renders the published stats in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let repo_stats = self.repo_stats.read().expect("metrics lock poisoned");
+        let mut output = String::new();
+        for family in &FAMILIES {
+            let _ = writeln!(output, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(output, "# TYPE {} {}", family.name, family.kind);
+            for (repo_name, stats) in repo_stats.iter() {
+                let _ = writeln!(
+                    output,
+                    "{}{{repo=\"{}\"}} {}",
+                    family.name,
+                    escape_label(repo_name),
+                    (family.value)(stats)
+                );
+            }
+        }
+        output
+    }
+}
+
+/// A metric exported per repository.
+struct Family {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&RepoStats) -> i32,
+}
+
+const FAMILIES: [Family; 3] = [
+    Family {
+        name: "devmetrics_pending_loc",
+        kind: "gauge",
+        help: "Lines added or deleted in uncommitted changes.",
+        value: |stats| stats.pending_additions + stats.pending_deletions,
+    },
+    Family {
+        name: "devmetrics_committed_additions_total",
+        kind: "counter",
+        help: "Lines added by today's commits.",
+        value: |stats| stats.committed_additions,
+    },
+    Family {
+        name: "devmetrics_committed_deletions_total",
+        kind: "counter",
+        help: "Lines deleted by today's commits.",
+        value: |stats| stats.committed_deletions,
+    },
+];
+
+/// Escapes a label value as required by the exposition format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serves `/metrics` on the given listener until the process exits.
+///
+/// # Arguments
+///
+/// * `listener` - The bound listener to accept scrapes on.
+/// * `metrics` - The Metrics updated by the watch loop.
+///
+/// # Returns
+///
+/// A Result that is only returned early if the server fails.
+pub async fn serve(listener: TcpListener, metrics: Metrics) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(metrics);
+
+    axum::serve(listener, app).await
+}
+
+async fn scrape(State(metrics): State<Metrics>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn metrics_endpoint_serves_the_latest_stats() {
+        let metrics = Metrics::default();
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            "api".to_string(),
+            RepoStats { committed_additions: 12, committed_deletions: 4, pending_additions: 3, ..Default::default() },
+        );
+        metrics.update(&repo_stats);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, metrics));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("# TYPE devmetrics_pending_loc gauge"));
+        assert!(response.contains("devmetrics_pending_loc{repo=\"api\"} 3"));
+        assert!(response.contains("devmetrics_committed_additions_total{repo=\"api\"} 12"));
+        assert!(response.contains("devmetrics_committed_deletions_total{repo=\"api\"} 4"));
+    }
+}
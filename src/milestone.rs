@@ -0,0 +1,166 @@
+//! Announcing when the day's committed LoC crosses a milestone.
+
+use chrono::NaiveDate;
+use serde_json::json;
+
+/// Remembers the highest milestone announced today so each one fires once.
+#[derive(Debug, Clone)]
+pub struct MilestoneTracker {
+    /// The LoC between two milestones.
+    step: u32,
+    /// The day and the milestone last announced on it.
+    last_announced: Option<(NaiveDate, i32)>,
+}
+
+impl MilestoneTracker {
+    /// Creates a tracker firing at every multiple of `step` committed LoC.
+    pub fn new(step: u32) -> Self {
+        MilestoneTracker { step, last_announced: None }
+    }
+
+    /// Records the day's committed LoC and returns the milestone just crossed, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `today` - The local day the LoC were committed on.
+    /// * `committed_loc` - The cumulative committed LoC of the day.
+    ///
+    /// # Returns
+    ///
+    /// The highest milestone reached, if it has not been announced today yet.
+    pub fn crossed(&mut self, today: NaiveDate, committed_loc: i32) -> Option<i32> {
+        let step = self.step as i32;
+        if step <= 0 {
+            return None;
+        }
+
+        let reached = committed_loc / step * step;
+        let announced = match self.last_announced {
+            Some((day, milestone)) if day == today => milestone,
+            _ => 0,
+        };
+        if reached <= announced {
+            return None;
+        }
+
+        self.last_announced = Some((today, reached));
+        Some(reached)
+    }
+}
+
+/// Describes a reached milestone, e.g. `Swifty hit 500 LoC today across 3 repos`.
+///
+/// # Arguments
+///
+/// * `author` - Who reached the milestone.
+/// * `milestone` - The committed LoC milestone reached.
+/// * `repos` - How many repositories contributed to it.
+pub fn message(author: &str, milestone: i32, repos: usize) -> String {
+    let repos = match repos {
+        1 => "1 repo".to_string(),
+        repos => format!("{} repos", repos),
+    };
+    format!("{} hit {} LoC today across {}", author, milestone, repos)
+}
+
+/// Shows a desktop notification for a milestone, doing nothing where
+/// notifications are unavailable such as on headless machines.
+///
+/// # Arguments
+///
+/// * `message` - The text of the notification.
+pub fn notify_desktop(message: String) {
+    // Talking to the notification daemon can block, so keep it off the watch loop
+    tokio::task::spawn_blocking(move || {
+        let _ = notify_rust::Notification::new()
+            .summary("DevMetrics")
+            .body(&message)
+            .show();
+    });
+}
+
+/// Posts a milestone to a Slack incoming webhook in the background, warning
+/// instead of failing when it cannot be delivered.
+///
+/// # Arguments
+///
+/// * `webhook` - The URL of the incoming webhook.
+/// * `message` - The text of the Slack message.
+pub fn post_to_slack(webhook: String, message: String) {
+    tokio::spawn(async move {
+        if let Err(e) = send_slack_message(&webhook, &message).await {
+            tracing::warn!(error = %e, "could not post milestone to Slack");
+        }
+    });
+}
+
+/// Sends a message to a Slack incoming webhook.
+///
+/// # Arguments
+///
+/// * `webhook` - The URL of the incoming webhook.
+/// * `message` - The text of the Slack message.
+///
+/// # Returns
+///
+/// A Result indicating whether the webhook accepted the message.
+async fn send_slack_message(webhook: &str, message: &str) -> Result<(), reqwest::Error> {
+    reqwest::Client::new()
+        .post(webhook)
+        .json(&json!({ "text": message }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use chrono::Duration;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn each_milestone_fires_once_per_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let mut tracker = MilestoneTracker::new(500);
+
+        assert_eq!(tracker.crossed(day, 120), None);
+        assert_eq!(tracker.crossed(day, 520), Some(500));
+        assert_eq!(tracker.crossed(day, 640), None);
+        assert_eq!(tracker.crossed(day, 520), None);
+        // Jumping past several milestones announces only the highest
+        assert_eq!(tracker.crossed(day, 1_730), Some(1_500));
+
+        let next_day = day + Duration::days(1);
+        assert_eq!(tracker.crossed(next_day, 80), None);
+        assert_eq!(tracker.crossed(next_day, 500), Some(500));
+
+        assert_eq!(message("Swifty", 500, 3), "Swifty hit 500 LoC today across 3 repos");
+        assert_eq!(message("Swifty", 1_000, 1), "Swifty hit 1000 LoC today across 1 repo");
+    }
+
+    #[tokio::test]
+    async fn slack_messages_are_posted_as_json_text() {
+        let (received_tx, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                received_tx.send(body).unwrap();
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let webhook = format!("http://{}/hook", addr);
+        send_slack_message(&webhook, "Swifty hit 500 LoC today across 3 repos").await.unwrap();
+        let body = received.recv().await.unwrap();
+        assert_eq!(body, json!({ "text": "Swifty hit 500 LoC today across 3 repos" }));
+
+        // A webhook that is not there is reported rather than panicking
+        assert!(send_slack_message(&format!("http://{}/missing", addr), "hi").await.is_err());
+    }
+}
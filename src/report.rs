@@ -0,0 +1,800 @@
+//! Reading back the changes stored by the watcher and summarizing them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+use crate::html;
+use crate::status::format_signed;
+use crate::store::{self, Store};
+use crate::{day_of, ChangeFilter, LocChange, RepoStats, ReportOpt};
+
+/// Runs the `report` subcommand, printing per-repository totals to stdout.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed report options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: ReportOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let day_start_hour = opt.day_start_hour;
+    let filter = ChangeFilter {
+        author: opt.author,
+        ..ChangeFilter::for_range_from(opt.since, opt.until, day_start_hour)?
+    };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+
+    let changes = store.query_range(&filter).await?;
+    store.close().await;
+
+    if let Some(path) = &opt.html {
+        let mut writer = BufWriter::new(File::create(path)?);
+        html::write_html(&summarize_by(&changes, GroupBy::Day, day_start_hour), &mut writer)?;
+        writer.flush()?;
+        println!("wrote the report to {}", path.display());
+        return Ok(());
+    }
+
+    if opt.by_weekday {
+        // Without bounds the range runs from the first stored day through today
+        let today = day_of(&Local::now(), day_start_hour);
+        let local_date = |instant: DateTime<Utc>| day_of(&instant.with_timezone(&Local), day_start_hour);
+        let first = filter
+            .since
+            .map(local_date)
+            .or_else(|| changes.iter().map(|change| local_date(change.timestamp)).min())
+            .unwrap_or(today);
+        let last = filter.until.map(|until| local_date(until - Duration::nanoseconds(1))).unwrap_or(today);
+        print!("{}", format_weekday_table(&weekday_totals(&changes, first, last, day_start_hour)));
+        return Ok(());
+    }
+
+    match opt.group_by {
+        Some(group_by) => print!("{}", format_grouped_table(&summarize_by(&changes, group_by, day_start_hour))),
+        None => print!("{}", format_table(&summarize(&changes, day_start_hour))),
+    }
+    Ok(())
+}
+
+/// The committed LoC of one day of the week over a range of days.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WeekdayTotal {
+    /// How many days of the range fall on this day of the week.
+    pub days: u32,
+    pub committed_additions: i32,
+    pub committed_deletions: i32,
+}
+
+impl WeekdayTotal {
+    /// The committed LoC on an average one of these days, if the range has any.
+    pub fn average(&self) -> Option<f64> {
+        match self.days {
+            0 => None,
+            days => Some(f64::from(self.committed_additions + self.committed_deletions) / f64::from(days)),
+        }
+    }
+}
+
+/// The periods `report --group-by` splits the totals into, and `compare --period` compares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Day,
+    /// ISO 8601 weeks, starting on Monday.
+    Week,
+    Month,
+}
+
+impl GroupBy {
+    /// Names the period a local day falls in, e.g. `2024-03-12`, `2024-W11` or `2024-03`.
+    /// The names sort in chronological order.
+    pub fn period_of(self, date: NaiveDate) -> String {
+        match self {
+            GroupBy::Day => date.format("%Y-%m-%d").to_string(),
+            GroupBy::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            GroupBy::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// The first local day of the period a local day falls in.
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GroupBy::Day => date,
+            GroupBy::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            GroupBy::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(GroupBy::Day),
+            "week" => Ok(GroupBy::Week),
+            "month" => Ok(GroupBy::Month),
+            other => Err(format!("unknown grouping `{}`", other)),
+        }
+    }
+}
+
+/// A `--since` or `--until` bound: a whole local day or an instant within one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBound {
+    /// A local calendar day, e.g. `2024-03-12`.
+    Day(NaiveDate),
+    /// An instant, given as an RFC 3339 timestamp or a local date and time.
+    At(DateTime<Utc>),
+}
+
+impl TimeBound {
+    /// Parses a bound, reading a bare time of day such as `13:00` as one on `today`.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - A date, a local time with or without date, or an RFC 3339 timestamp.
+    /// * `today` - The local day a bare time of day falls on.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the bound, or an error if the value is not understood
+    /// or names a local time that happens twice or not at all around a DST switch.
+    pub fn parse_on(value: &str, today: NaiveDate) -> Result<Self, String> {
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(TimeBound::Day(date));
+        }
+        if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+            return Ok(TimeBound::At(instant.with_timezone(&Utc)));
+        }
+
+        let local = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .or_else(|| {
+                ["%H:%M:%S", "%H:%M"]
+                    .iter()
+                    .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+                    .map(|time| today.and_time(time))
+            })
+            .ok_or_else(|| {
+                format!("`{}` is not a date like 2024-03-12, a time like 13:00 or an RFC 3339 timestamp", value)
+            })?;
+        match local.and_local_timezone(Local) {
+            LocalResult::Single(instant) => Ok(TimeBound::At(instant.with_timezone(&Utc))),
+            LocalResult::Ambiguous(..) => {
+                Err(format!("`{}` happens twice as the clocks go back; add a UTC offset such as +01:00", value))
+            }
+            LocalResult::None => Err(format!("`{}` does not exist in local time as the clocks go forward", value)),
+        }
+    }
+
+    /// The first instant the bound includes, when it is a lower bound, with days
+    /// starting at `day_start_hour`.
+    pub fn start(self, day_start_hour: u32) -> DateTime<Utc> {
+        match self {
+            TimeBound::Day(date) => start_of_day(date, day_start_hour),
+            TimeBound::At(instant) => instant,
+        }
+    }
+
+    /// The first instant past the bound, when it is an upper bound; a day is included in full.
+    pub fn end(self, day_start_hour: u32) -> DateTime<Utc> {
+        match self {
+            TimeBound::Day(date) => start_of_day(date + Duration::days(1), day_start_hour),
+            TimeBound::At(instant) => instant,
+        }
+    }
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        TimeBound::parse_on(value, Local::now().date_naive())
+    }
+}
+
+impl std::fmt::Display for TimeBound {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimeBound::Day(date) => write!(f, "{}", date),
+            TimeBound::At(instant) => write!(f, "{}", instant.with_timezone(&Local).to_rfc3339()),
+        }
+    }
+}
+
+/// Returns the UTC instant at which the given local calendar day begins.
+///
+/// # Arguments
+///
+/// * `date` - The local calendar day.
+#[cfg(test)]
+pub fn start_of_local_day(date: NaiveDate) -> DateTime<Utc> {
+    start_of_day(date, 0)
+}
+
+/// Returns the UTC instant at which the given local day begins when days start
+/// at `day_start_hour`, as with `--day-start-hour`.
+///
+/// # Arguments
+///
+/// * `date` - The local day, named after the calendar day it starts on.
+/// * `day_start_hour` - The hour days start at, 0 for midnight.
+pub fn start_of_day(date: NaiveDate, day_start_hour: u32) -> DateTime<Utc> {
+    let start = date.and_time(NaiveTime::MIN) + Duration::hours(day_start_hour.into());
+    // The start can fall inside a DST gap, in which case the day starts an hour later
+    start
+        .and_local_timezone(Local)
+        .earliest()
+        .or_else(|| (start + Duration::hours(1)).and_local_timezone(Local).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| start.and_utc())
+}
+
+/// Totals the stored rows per repository, author, local day and committed flag.
+///
+/// Rows of single commits are added up. The other rows are snapshots of the
+/// running totals, of which only the day's latest counts. Rows of the
+/// per-extension and per-category breakdown are skipped as they repeat the totals, and flagged
+/// commits are skipped as they are not counted.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to total.
+///
+/// # Returns
+///
+/// One row per group, dated at its latest stored row.
+pub fn daily_totals(changes: &[LocChange]) -> Vec<LocChange> {
+    daily_totals_from(changes, 0)
+}
+
+/// Totals the stored rows like `daily_totals`, with days starting at `day_start_hour`.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to total.
+/// * `day_start_hour` - The hour days start at, so earlier rows count for the day before.
+///
+/// # Returns
+///
+/// One row per group, dated at its latest stored row.
+pub fn daily_totals_from(changes: &[LocChange], day_start_hour: u32) -> Vec<LocChange> {
+    let mut snapshots: HashMap<DayKey, &LocChange> = HashMap::new();
+    let mut counted: Vec<(DayKey, &LocChange)> = Vec::new();
+
+    for change in changes.iter().filter(|change| change.is_total() && !change.flagged) {
+        let key = (
+            change.repo_name.as_str(),
+            change.author.as_deref(),
+            day_of(&change.timestamp.with_timezone(&Local), day_start_hour),
+            change.is_committed,
+        );
+        if change.commit_sha.is_some() {
+            counted.push((key, change));
+            continue;
+        }
+        snapshots
+            .entry(key)
+            .and_modify(|current| {
+                if change.timestamp >= current.timestamp {
+                    *current = change;
+                }
+            })
+            .or_insert(change);
+    }
+    counted.extend(snapshots);
+
+    let mut totals: HashMap<DayKey, LocChange> = HashMap::new();
+    for (key, change) in counted {
+        let total = totals.entry(key).or_insert_with(|| LocChange {
+            additions: 0,
+            deletions: 0,
+            commit_sha: None,
+            staged_additions: 0,
+            staged_deletions: 0,
+            files_changed: 0,
+            inserted_lines: 0,
+            edited_lines: 0,
+            flagged: false,
+            session_id: None,
+            is_merge: false,
+            files: Vec::new(),
+            ..change.clone()
+        });
+        total.additions += change.additions;
+        total.deletions += change.deletions;
+        total.staged_additions += change.staged_additions;
+        total.staged_deletions += change.staged_deletions;
+        total.files_changed += change.files_changed;
+        if change.timestamp > total.timestamp {
+            total.timestamp = change.timestamp;
+            total.branch = change.branch.clone();
+        }
+    }
+
+    totals.into_values().collect()
+}
+
+/// The repository, author, local day and committed flag rows are totalled by.
+type DayKey<'a> = (&'a str, Option<&'a str>, NaiveDate, bool);
+
+/// Sums stored changes into per-repository totals.
+///
+/// The rows are first totalled per day with `daily_totals_from`.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to summarize.
+/// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A map from repository name to its totals, ordered by name.
+pub fn summarize(changes: &[LocChange], day_start_hour: u32) -> BTreeMap<String, RepoStats> {
+    let mut totals: BTreeMap<String, RepoStats> = BTreeMap::new();
+    for change in daily_totals_from(changes, day_start_hour) {
+        add_total(totals.entry(change.repo_name.clone()).or_default(), &change);
+    }
+
+    totals
+}
+
+/// Restores the stats of a day from the stored rows, so a restarted watcher shows
+/// them before its first poll.
+///
+/// # Arguments
+///
+/// * `store` - The Store holding the changes.
+/// * `day` - The local day to restore.
+/// * `day_start_hour` - The hour the day starts at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A Result containing the day's committed totals and latest pending work per
+/// repository and author.
+pub async fn load_latest_stats(
+    store: &dyn Store,
+    day: NaiveDate,
+    day_start_hour: u32,
+) -> Result<BTreeMap<(String, String), RepoStats>, sqlx::Error> {
+    let filter = ChangeFilter {
+        since: Some(start_of_day(day, day_start_hour)),
+        until: Some(start_of_day(day + Duration::days(1), day_start_hour)),
+        ..Default::default()
+    };
+    let mut totals: BTreeMap<(String, String), RepoStats> = BTreeMap::new();
+    for change in daily_totals_from(&store.query_range(&filter).await?, day_start_hour) {
+        let key = (change.repo_name.clone(), change.author.clone().unwrap_or_default());
+        add_total(totals.entry(key).or_default(), &change);
+    }
+
+    Ok(totals)
+}
+
+/// Sums stored changes into per-repository totals for each period.
+///
+/// Rows are totalled per day with `daily_totals_from` and each day is put in the
+/// period its local day falls in, so a period starts at the same hour a day does.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to summarize.
+/// * `group_by` - The kind of period to split the totals by.
+/// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A map from period name to the per-repository totals of that period, both ordered.
+pub fn summarize_by(
+    changes: &[LocChange],
+    group_by: GroupBy,
+    day_start_hour: u32,
+) -> BTreeMap<String, BTreeMap<String, RepoStats>> {
+    let mut periods: BTreeMap<String, BTreeMap<String, RepoStats>> = BTreeMap::new();
+    for change in daily_totals_from(changes, day_start_hour) {
+        let period = group_by.period_of(day_of(&change.timestamp.with_timezone(&Local), day_start_hour));
+        let stats = periods.entry(period).or_default().entry(change.repo_name.clone()).or_default();
+        add_total(stats, &change);
+    }
+
+    periods
+}
+
+/// Sums the committed changes by the local day of the week they were made on.
+///
+/// Every day of the range counts towards its weekday's average, including the
+/// days without commits, so a range shorter than a week leaves some weekdays
+/// without days at all.
+///
+/// # Arguments
+///
+/// * `changes` - The stored changes to sum.
+/// * `first` - The first local day of the range.
+/// * `last` - The last local day of the range, included.
+/// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// The totals from Monday to Sunday.
+pub fn weekday_totals(
+    changes: &[LocChange],
+    first: NaiveDate,
+    last: NaiveDate,
+    day_start_hour: u32,
+) -> [WeekdayTotal; 7] {
+    let mut totals = [WeekdayTotal::default(); 7];
+    for day in first.iter_days().take_while(|day| *day <= last) {
+        totals[day.weekday().num_days_from_monday() as usize].days += 1;
+    }
+
+    for change in daily_totals_from(changes, day_start_hour).iter().filter(|change| change.is_committed) {
+        let day = day_of(&change.timestamp.with_timezone(&Local), day_start_hour);
+        if day < first || day > last {
+            continue;
+        }
+        let total = &mut totals[day.weekday().num_days_from_monday() as usize];
+        total.committed_additions += change.additions;
+        total.committed_deletions += change.deletions;
+    }
+
+    totals
+}
+
+fn add_total(stats: &mut RepoStats, change: &LocChange) {
+    if change.is_committed {
+        stats.committed_additions += change.additions;
+        stats.committed_deletions += change.deletions;
+        stats.committed_files += change.files_changed;
+    } else {
+        stats.pending_additions += change.additions;
+        stats.pending_deletions += change.deletions;
+        stats.staged_additions += change.staged_additions;
+        stats.staged_deletions += change.staged_deletions;
+        stats.pending_files += change.files_changed;
+    }
+}
+
+/// The headers of the columns holding the stats.
+const STAT_HEADERS: [&str; 6] = ["Committed +", "Committed -", "Pending +", "Pending -", "Net", "Churn"];
+
+/// Formats per-repository totals as an aligned table with a grand total row.
+/// The net and churn columns cover committed and pending changes together.
+///
+/// # Arguments
+///
+/// * `totals` - The per-repository totals to format.
+pub fn format_table(totals: &BTreeMap<String, RepoStats>) -> String {
+    let mut grand_total = RepoStats::default();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (repo_name, stats) in totals {
+        grand_total += stats;
+        rows.push(table_row(&[repo_name], stats));
+    }
+    rows.push(table_row(&["Total"], &grand_total));
+
+    align_table(&["Repository"], &STAT_HEADERS, &rows)
+}
+
+/// Formats per-period totals as an aligned table with a row per period and
+/// repository, followed by a grand total row.
+///
+/// # Arguments
+///
+/// * `periods` - The per-repository totals of each period, from `summarize_by`.
+pub fn format_grouped_table(periods: &BTreeMap<String, BTreeMap<String, RepoStats>>) -> String {
+    let mut grand_total = RepoStats::default();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for (period, totals) in periods {
+        for (repo_name, stats) in totals {
+            grand_total += stats;
+            rows.push(table_row(&[period, repo_name], stats));
+        }
+    }
+    rows.push(table_row(&["Total", ""], &grand_total));
+
+    align_table(&["Period", "Repository"], &STAT_HEADERS, &rows)
+}
+
+/// Formats the weekday totals as an aligned table from Monday to Sunday, with the
+/// average committed LoC per day, or `-` for a weekday the range does not include.
+///
+/// # Arguments
+///
+/// * `totals` - The totals from Monday to Sunday, from `weekday_totals`.
+pub fn format_weekday_table(totals: &[WeekdayTotal; 7]) -> String {
+    let rows: Vec<Vec<String>> = totals
+        .iter()
+        .zip(["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"])
+        .map(|(total, weekday)| {
+            vec![
+                weekday.to_string(),
+                total.days.to_string(),
+                total.committed_additions.to_string(),
+                total.committed_deletions.to_string(),
+                total.average().map_or_else(|| "-".to_string(), |average| format!("{:.1}", average)),
+            ]
+        })
+        .collect();
+
+    align_table(&["Weekday"], &["Days", "Committed +", "Committed -", "Average LoC"], &rows)
+}
+
+/// Lines up the rows under the headers, the label columns aligned left and the
+/// stat columns aligned right.
+///
+/// # Arguments
+///
+/// * `labels` - The headers of the label columns each row starts with.
+/// * `stats` - The headers of the stat columns that follow.
+/// * `rows` - The cells of each row.
+pub fn align_table(labels: &[&str], stats: &[&str], rows: &[Vec<String>]) -> String {
+    let header: Vec<String> = labels.iter().chain(stats).map(|header| header.to_string()).collect();
+
+    let mut widths: Vec<usize> = header.iter().map(String::len).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut output = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let mut line = String::new();
+        for (column, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if column > 0 {
+                line.push_str("  ");
+            }
+            match column < labels.len() {
+                true => line.push_str(&format!("{:<width$}", cell, width = width)),
+                false => line.push_str(&format!("{:>width$}", cell, width = width)),
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+fn table_row(labels: &[&str], stats: &RepoStats) -> Vec<String> {
+    let mut row: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+    row.extend([
+        stats.committed_additions.to_string(),
+        stats.committed_deletions.to_string(),
+        stats.pending_additions.to_string(),
+        stats.pending_deletions.to_string(),
+        format_signed(stats.net()),
+        stats.churn().to_string(),
+    ]);
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(repo: &str, timestamp: DateTime<Utc>, additions: i32, is_committed: bool) -> LocChange {
+        LocChange {
+            repo_name: repo.to_string(),
+            timestamp,
+            author: Some("Me".to_string()),
+            author_email: None,
+            additions,
+            deletions: 0,
+            is_committed,
+            extension: None,
+            category: None,
+            branch: None,
+            commit_sha: None,
+            staged_additions: 0,
+            staged_deletions: 0,
+            files_changed: 0,
+            inserted_lines: 0,
+            edited_lines: 0,
+            flagged: false,
+            session_id: None,
+            is_merge: false,
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn summarize_counts_only_the_latest_snapshot_per_day() {
+        let day_start = start_of_local_day(Local::now().date_naive());
+        let changes = [
+            change("api", day_start, 5, true),
+            change("api", day_start + Duration::minutes(1), 12, true),
+            change("api", day_start, 3, false),
+            change("api", day_start - Duration::days(2), 7, true),
+            LocChange { extension: Some("rs".to_string()), ..change("api", day_start, 99, true) },
+        ];
+
+        let totals = summarize(&changes, 0);
+        let api = &totals["api"];
+        assert_eq!(api.committed_additions, 19);
+        assert_eq!(api.pending_additions, 3);
+    }
+
+    #[test]
+    fn each_weekday_averages_over_its_days_in_the_range() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let at = |d, hours| start_of_local_day(day(d)) + Duration::hours(hours);
+        let changes = [
+            // Two Mondays, the first with two commits
+            LocChange { commit_sha: Some("a1".to_string()), ..change("api", at(11, 9), 4, true) },
+            LocChange { commit_sha: Some("a2".to_string()), ..change("web", at(11, 17), 6, true) },
+            LocChange { commit_sha: Some("a3".to_string()), ..change("api", at(18, 10), 30, true) },
+            // Late on a Wednesday in local time, and pending work that does not count
+            LocChange { commit_sha: Some("a4".to_string()), ..change("api", at(13, 23), 9, true) },
+            change("api", at(12, 12), 50, false),
+        ];
+
+        // Two full weeks, from Monday the 11th through Sunday the 24th
+        let totals = weekday_totals(&changes, day(11), day(24), 0);
+        assert_eq!(totals[0], WeekdayTotal { days: 2, committed_additions: 40, committed_deletions: 0 });
+        assert_eq!(totals[0].average(), Some(20.0));
+        assert_eq!(totals[1].average(), Some(0.0));
+        assert_eq!(totals[2].committed_additions, 9);
+
+        let table = format_weekday_table(&totals);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "Weekday    Days  Committed +  Committed -  Average LoC");
+        assert_eq!(lines[1], "Monday        2           40            0         20.0");
+        assert_eq!(lines[3], "Wednesday     2            9            0          4.5");
+
+        // A range shorter than a week has nothing to average for the other weekdays
+        let short = weekday_totals(&changes, day(18), day(19), 0);
+        assert_eq!(short[0].average(), Some(30.0));
+        assert_eq!(short[2].average(), None);
+        assert!(format_weekday_table(&short).lines().nth(3).unwrap().ends_with("  -"));
+    }
+
+    #[test]
+    fn days_land_in_the_period_they_fall_in() {
+        let at_noon = |y, m, d| start_of_local_day(NaiveDate::from_ymd_opt(y, m, d).unwrap()) + Duration::hours(12);
+        let changes = [
+            // Sunday and Monday either side of a week boundary
+            change("api", at_noon(2024, 3, 10), 5, true),
+            change("api", at_noon(2024, 3, 11), 7, true),
+            change("api", at_noon(2024, 3, 12), 2, false),
+            // The last days of the year already belong to the first ISO week of the next
+            change("web", at_noon(2024, 12, 29), 1, true),
+            change("web", at_noon(2024, 12, 30), 3, true),
+        ];
+
+        let weeks = summarize_by(&changes, GroupBy::Week, 0);
+        let names: Vec<&str> = weeks.keys().map(String::as_str).collect();
+        assert_eq!(names, ["2024-W10", "2024-W11", "2024-W52", "2025-W01"]);
+        assert_eq!(weeks["2024-W10"]["api"].committed_additions, 5);
+        assert_eq!(weeks["2024-W11"]["api"].committed_additions, 7);
+        assert_eq!(weeks["2024-W11"]["api"].pending_additions, 2);
+        assert_eq!(weeks["2025-W01"]["web"].committed_additions, 3);
+
+        let months = summarize_by(&changes, GroupBy::Month, 0);
+        assert_eq!(months["2024-03"]["api"].committed_additions, 12);
+        assert_eq!(months["2024-12"]["web"].committed_additions, 4);
+        assert_eq!(summarize_by(&changes, GroupBy::Day, 0).len(), 5);
+
+        let table = format_grouped_table(&weeks);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("Period    Repository  Committed +"));
+        assert!(lines[2].starts_with("2024-W11  api                   7"));
+        assert!(lines[5].starts_with("Total                          16"));
+    }
+
+    #[test]
+    fn table_columns_are_aligned() {
+        let mut totals = BTreeMap::new();
+        totals.insert("a".to_string(), RepoStats { committed_additions: 1234, ..Default::default() });
+        totals.insert("long-repo-name".to_string(), RepoStats { pending_deletions: 5, ..Default::default() });
+
+        let table = format_table(&totals);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("Repository      Committed +"));
+        assert!(lines[1].starts_with("a                      1234"));
+        assert!(lines[3].starts_with("Total                  1234"));
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert!(lines[2].ends_with("   -5      5"));
+        assert!(lines[3].ends_with("+1229   1239"));
+    }
+
+    #[tokio::test]
+    async fn latest_stats_are_rebuilt_from_the_stored_rows() {
+        let store = crate::store::SqliteStore::memory().await;
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let day_start = start_of_local_day(day);
+        let rows = [
+            LocChange { commit_sha: Some("a1".to_string()), ..change("api", day_start + Duration::hours(9), 10, true) },
+            LocChange { commit_sha: Some("a2".to_string()), ..change("api", day_start + Duration::hours(10), 4, true) },
+            change("api", day_start + Duration::hours(9), 30, false),
+            change("api", day_start + Duration::hours(11), 6, false),
+            change("web", day_start + Duration::hours(11), 2, false),
+            // The day before is not part of the restored stats
+            change("old", day_start - Duration::hours(1), 50, false),
+        ];
+        for row in &rows {
+            store.store_change(row).await.unwrap();
+        }
+
+        let stats = load_latest_stats(&store, day, 0).await.unwrap();
+        let key = |repo: &str| (repo.to_string(), "Me".to_string());
+        assert_eq!(stats.keys().cloned().collect::<Vec<_>>(), [key("api"), key("web")]);
+        assert_eq!(stats[&key("api")].committed_additions, 14);
+        assert_eq!(stats[&key("api")].pending_additions, 6);
+        assert_eq!(stats[&key("web")].pending_additions, 2);
+    }
+
+    #[tokio::test]
+    async fn additions_and_deletions_are_shown_as_stored() {
+        use crate::status::{self, DisplayOptions, OutputFormat};
+
+        let store = crate::store::SqliteStore::memory().await;
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let at = start_of_local_day(day) + Duration::hours(9);
+        let rows = [
+            LocChange { commit_sha: Some("a1".to_string()), deletions: 40, ..change("api", at, 12, true) },
+            LocChange { deletions: 3, ..change("api", at, 5, false) },
+            LocChange { commit_sha: Some("b1".to_string()), deletions: 7, ..change("web", at, 1, true) },
+        ];
+        for row in &rows {
+            store.store_change(row).await.unwrap();
+        }
+
+        let stored = store.query_range(&ChangeFilter::default()).await.unwrap();
+        let (additions, deletions): (i32, i32) =
+            stored.iter().fold((0, 0), |(adds, dels), row| (adds + row.additions, dels + row.deletions));
+        assert_eq!((additions, deletions), (18, 50));
+
+        let stats = load_latest_stats(&store, day, 0).await.unwrap();
+        let text = status::render(&stats, &DisplayOptions::default(), Utc::now(), 0, None);
+        assert!(text.lines().any(|line| line == "Lines: +18 / -50"));
+
+        let display = DisplayOptions { format: OutputFormat::Json, ..Default::default() };
+        let json = status::render(&stats, &display, Utc::now(), 0, None);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!((value["additions"].as_i64(), value["deletions"].as_i64()), (Some(18), Some(50)));
+    }
+
+    #[tokio::test]
+    async fn a_time_of_day_bound_splits_the_day() {
+        let store = crate::store::SqliteStore::memory().await;
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let day_start = start_of_local_day(day);
+        let lunch = change("api", day_start + Duration::minutes(12 * 60 + 30), 5, false);
+        let afternoon = change("api", day_start + Duration::minutes(13 * 60 + 30), 8, false);
+        for row in [&lunch, &afternoon] {
+            store.store_change(row).await.unwrap();
+        }
+
+        let since = TimeBound::parse_on("13:00", day).unwrap();
+        assert_eq!(since, TimeBound::At(day_start + Duration::hours(13)));
+        let filter = ChangeFilter::for_range(Some(since), None).unwrap();
+        let changes = store.query_range(&filter).await.unwrap();
+        assert_eq!(changes.iter().map(|change| change.additions).collect::<Vec<_>>(), [8]);
+
+        // A timestamp with an offset, and a day included in full
+        let until = TimeBound::parse_on(&(day_start + Duration::hours(13)).to_rfc3339(), day).unwrap();
+        let filter = ChangeFilter::for_range(Some(TimeBound::Day(day)), Some(until)).unwrap();
+        let changes = store.query_range(&filter).await.unwrap();
+        assert_eq!(changes.iter().map(|change| change.additions).collect::<Vec<_>>(), [5]);
+        let filter = ChangeFilter::for_range(None, Some(TimeBound::parse_on("2024-03-12", day).unwrap())).unwrap();
+        assert_eq!(store.query_range(&filter).await.unwrap().len(), 2);
+
+        assert!(TimeBound::parse_on("1pm", day).is_err());
+        assert!(TimeBound::parse_on("12/03/2024", day).is_err());
+        assert!(ChangeFilter::for_range(Some(since), Some(TimeBound::parse_on("12:00", day).unwrap())).is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! Aggregation of the raw per-poll rows into one summary row per day, and the
+//! pruning of the raw rows older than `--retain-days`.
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Local, NaiveDate};
+use tracing::{debug, info};
+
+use crate::report::{daily_totals_from, start_of_day};
+use crate::store::{self, Store};
+use crate::{day_of, ChangeFilter, LocChange, RollupOpt};
+
+/// A row of the daily_summary table.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub repo_name: String,
+    pub author: String,
+    pub committed_additions: i32,
+    pub committed_deletions: i32,
+    /// The largest pending LoC (additions plus deletions) seen during the day.
+    pub pending_max: i32,
+}
+
+/// Runs the `rollup` subcommand, summarizing every day in the requested range.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed rollup options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: RollupOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+
+    let day_start_hour = opt.day_start_hour;
+    let since = match opt.since {
+        Some(since) => Some(since),
+        None => store
+            .first_timestamp()
+            .await?
+            .map(|timestamp| day_of(&timestamp.with_timezone(&Local), day_start_hour)),
+    };
+
+    let until = opt.until.unwrap_or_else(|| day_of(&Local::now(), day_start_hour));
+    let mut days = 0;
+    let mut rows = 0;
+    if let Some(mut day) = since {
+        while day <= until {
+            rows += rollup_day(store.as_ref(), day, day_start_hour).await?.len();
+            days += 1;
+            day += Duration::days(1);
+        }
+    }
+    store.close().await;
+
+    println!("Rolled up {} days into {} summary rows", days, rows);
+    Ok(())
+}
+
+/// Aggregates the stored changes of one local day into the daily_summary table,
+/// replacing any summary previously written for that day.
+///
+/// A day without stored changes, such as one pruned by `--retain-days`, keeps
+/// the summaries it has.
+///
+/// # Arguments
+///
+/// * `store` - The Store holding the changes and summaries.
+/// * `date` - The local day to summarize.
+/// * `day_start_hour` - The hour the day starts at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A Result containing the summary rows written for the day, empty if it was skipped.
+pub async fn rollup_day(
+    store: &dyn Store,
+    date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Vec<DailySummary>, sqlx::Error> {
+    let summaries = match summarize_day(store, date, day_start_hour).await? {
+        Some(summaries) => summaries,
+        None => {
+            debug!(day = %date, "no changes stored, keeping the day's summaries");
+            return Ok(Vec::new());
+        }
+    };
+    store.replace_daily_summaries(date, &summaries).await?;
+
+    Ok(summaries)
+}
+
+/// Computes the summaries of one local day from its stored changes.
+///
+/// # Arguments
+///
+/// * `store` - The Store holding the changes.
+/// * `date` - The local day to summarize.
+/// * `day_start_hour` - The hour the day starts at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A Result containing the summary rows of the day, or None if it has no stored changes.
+async fn summarize_day(
+    store: &dyn Store,
+    date: NaiveDate,
+    day_start_hour: u32,
+) -> Result<Option<Vec<DailySummary>>, sqlx::Error> {
+    let filter = ChangeFilter {
+        since: Some(start_of_day(date, day_start_hour)),
+        until: Some(start_of_day(date + Duration::days(1), day_start_hour)),
+        ..Default::default()
+    };
+    let changes = store.query_range(&filter).await?;
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut summaries: BTreeMap<(String, String), DailySummary> = BTreeMap::new();
+
+    for change in daily_totals_from(&changes, day_start_hour).iter().filter(|change| change.is_committed) {
+        let summary = summary_entry(&mut summaries, date, change);
+        summary.committed_additions = change.additions;
+        summary.committed_deletions = change.deletions;
+    }
+
+    for change in changes
+        .iter()
+        .filter(|change| !change.is_committed && change.is_total())
+    {
+        let summary = summary_entry(&mut summaries, date, change);
+        summary.pending_max = summary.pending_max.max(change.additions + change.deletions);
+    }
+
+    Ok(Some(summaries.into_values().collect()))
+}
+
+/// Deletes the stored changes of the days older than the retention period,
+/// rolling those days up into the daily_summary table first if asked to.
+///
+/// The summaries are written in the transaction that deletes the changes, so
+/// either both happen or neither does.
+///
+/// # Arguments
+///
+/// * `store` - The Store holding the changes and summaries.
+/// * `retain_days` - How many local days before `today` keep their changes.
+/// * `roll_up` - Whether to summarize the days about to be pruned.
+/// * `today` - The local day the retention period is counted back from.
+/// * `day_start_hour` - The hour days start at, from `--day-start-hour`.
+///
+/// # Returns
+///
+/// A Result containing the number of changes deleted.
+pub async fn prune_old_changes(
+    store: &dyn Store,
+    retain_days: u32,
+    roll_up: bool,
+    today: NaiveDate,
+    day_start_hour: u32,
+) -> Result<u64, sqlx::Error> {
+    let first_kept = today - Duration::days(retain_days.into());
+    let mut summaries = Vec::new();
+    if roll_up {
+        if let Some(first) = store.first_timestamp().await? {
+            let mut day = day_of(&first.with_timezone(&Local), day_start_hour);
+            while day < first_kept {
+                summaries.extend(summarize_day(store, day, day_start_hour).await?.unwrap_or_default());
+                day += Duration::days(1);
+            }
+        }
+    }
+
+    let deleted = store.prune_before(start_of_day(first_kept, day_start_hour), &summaries).await?;
+    match deleted {
+        0 => debug!(before = %first_kept, "no changes old enough to prune"),
+        _ => info!(deleted, before = %first_kept, rolled_up = roll_up, "pruned old changes"),
+    }
+    Ok(deleted)
+}
+
+/// Returns the summary a change contributes to, creating an empty one if needed.
+fn summary_entry<'a>(
+    summaries: &'a mut BTreeMap<(String, String), DailySummary>,
+    date: NaiveDate,
+    change: &LocChange,
+) -> &'a mut DailySummary {
+    let author = change.author.clone().unwrap_or_default();
+    summaries
+        .entry((change.repo_name.clone(), author.clone()))
+        .or_insert_with(|| DailySummary {
+            date,
+            repo_name: change.repo_name.clone(),
+            author,
+            committed_additions: 0,
+            committed_deletions: 0,
+            pending_max: 0,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::start_of_local_day;
+    use crate::store::SqliteStore;
+    use crate::{changes_from_stats, RepoStats};
+    use sqlx::sqlite::SqlitePool;
+    use structopt::StructOpt;
+    use tempfile::TempDir;
+
+    /// Reads back the daily summaries between two days, inclusive.
+    async fn query_daily_summaries(
+        pool: &SqlitePool,
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> Result<Vec<DailySummary>, sqlx::Error> {
+        sqlx::query_as::<_, DailySummary>(
+            r#"
+            SELECT date, repo_name, author, committed_additions, committed_deletions, pending_max
+            FROM daily_summary
+            WHERE date >= $1 AND date <= $2
+            ORDER BY date, repo_name, author
+            "#
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(pool)
+        .await
+    }
+
+    #[tokio::test]
+    async fn rollup_day_sums_the_days_rows() {
+        let store = SqliteStore::memory().await;
+
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let start = start_of_local_day(day);
+        let commits = [
+            ("c1", Duration::hours(9), 10, 1),
+            ("c2", Duration::hours(12), 15, 2),
+            ("c3", Duration::hours(17), 15, 2),
+            // The next day must not leak into the summary
+            ("c4", Duration::hours(26), 99, 99),
+        ];
+        let pending = [4, 30, 2, 99];
+        for (polled, pending_additions) in pending.into_iter().enumerate() {
+            // Every poll lists the commits made so far, which are stored only once
+            let mut stats = RepoStats { pending_additions, ..Default::default() };
+            for (sha, offset, additions, deletions) in &commits[..=polled] {
+                stats += &RepoStats::with_commit(sha, start + *offset, *additions, *deletions);
+            }
+            for change in &changes_from_stats("api", "Me", None, &stats, start + commits[polled].1) {
+                store.store_change(change).await.unwrap();
+            }
+        }
+
+        let written = rollup_day(&store, day, 0).await.unwrap();
+        assert_eq!(written.len(), 1);
+
+        // Rolling up twice replaces rather than duplicates the summary
+        rollup_day(&store, day, 0).await.unwrap();
+        let stored = query_daily_summaries(&store.pool(), day, day).await.unwrap();
+        assert_eq!(stored, written);
+
+        let summary = &stored[0];
+        assert_eq!(summary.repo_name, "api");
+        assert_eq!(summary.author, "Me");
+        assert_eq!(summary.committed_additions, 40);
+        assert_eq!(summary.committed_deletions, 5);
+        assert_eq!(summary.pending_max, 30);
+    }
+
+    #[tokio::test]
+    async fn the_rollup_subcommand_starts_days_at_the_day_start_hour() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("stats.db");
+        let store = SqliteStore::open(&db_path).await.unwrap();
+        store.setup().await.unwrap();
+        // 1am on the 13th, still the 12th when days start at 4am
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let at = start_of_local_day(day + Duration::days(1)) + Duration::hours(1);
+        let stats = RepoStats::with_commit("late", at, 6, 0);
+        store.store_changes(&changes_from_stats("api", "Me", None, &stats, at)).await.unwrap();
+
+        let args = ["rollup", "--since", "2024-03-12", "--until", "2024-03-13", "--day-start-hour", "4", "--db-path"];
+        run(RollupOpt::from_iter(args.iter().copied().chain([db_path.to_str().unwrap()]))).await.unwrap();
+        let stored = query_daily_summaries(&store.pool(), day, day + Duration::days(1)).await.unwrap();
+        let days: Vec<(NaiveDate, i32)> =
+            stored.iter().map(|summary| (summary.date, summary.committed_additions)).collect();
+        assert_eq!(days, [(day, 6)]);
+    }
+
+    #[tokio::test]
+    async fn pruning_rolls_up_and_deletes_only_the_days_past_the_retention() {
+        let store = SqliteStore::memory().await;
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let old_day = today - Duration::days(10);
+        let kept_day = today - Duration::days(7);
+        for (sha, day) in [("old", old_day), ("kept", kept_day)] {
+            let at = start_of_local_day(day) + Duration::hours(12);
+            let stats = RepoStats::with_commit(sha, at, 8, 2);
+            store.store_changes(&changes_from_stats("api", "Me", None, &stats, at)).await.unwrap();
+        }
+
+        assert_eq!(prune_old_changes(&store, 7, true, today, 0).await.unwrap(), 2);
+        let changes = store.query_range(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|change| change.timestamp >= start_of_local_day(kept_day)));
+
+        // The pruned day keeps its summary, the kept one is left to the daily rollup
+        let summaries = query_daily_summaries(&store.pool(), old_day, today).await.unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!((summaries[0].date, summaries[0].committed_additions), (old_day, 8));
+
+        // Rolling the pruned day up again finds no changes and keeps its summary
+        assert!(rollup_day(&store, old_day, 0).await.unwrap().is_empty());
+        let summaries = query_daily_summaries(&store.pool(), old_day, old_day).await.unwrap();
+        assert_eq!((summaries.len(), summaries[0].committed_additions), (1, 8));
+    }
+}
@@ -0,0 +1,749 @@
+//! Rendering of the status printed by the watch loop after each poll.
+
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, Utc};
+use owo_colors::{AnsiColors, OwoColorize};
+use serde::Serialize;
+
+use crate::RepoStats;
+
+/// The width of the daily goal's progress bar, in characters.
+const GOAL_BAR_WIDTH: i64 = 20;
+
+/// The format used to print the per-poll status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable lines, one per repository plus a total.
+    #[default]
+    Text,
+    /// A single JSON object per poll, one per line.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!("unknown output format `{}`", other)),
+        }
+    }
+}
+
+/// How deleted lines count towards the LoC shown and the daily goal. The
+/// stored additions and deletions are never affected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeletionsAs {
+    /// Deleting a line counts as much as writing one.
+    #[default]
+    Positive,
+    /// Deleting a line takes back a written one.
+    Negative,
+    /// Only written lines count.
+    Ignore,
+}
+
+impl DeletionsAs {
+    /// Combines added and deleted lines into the LoC shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `additions` - The lines added.
+    /// * `deletions` - The lines deleted.
+    pub fn loc(self, additions: i32, deletions: i32) -> i32 {
+        match self {
+            DeletionsAs::Positive => additions + deletions,
+            DeletionsAs::Negative => additions - deletions,
+            DeletionsAs::Ignore => additions,
+        }
+    }
+
+    /// The committed LoC of the stats.
+    pub fn committed(self, stats: &RepoStats) -> i32 {
+        self.loc(stats.committed_additions, stats.committed_deletions)
+    }
+
+    /// The pending LoC of the stats.
+    pub fn pending(self, stats: &RepoStats) -> i32 {
+        self.loc(stats.pending_additions, stats.pending_deletions)
+    }
+}
+
+impl FromStr for DeletionsAs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "positive" => Ok(DeletionsAs::Positive),
+            "negative" => Ok(DeletionsAs::Negative),
+            "ignore" => Ok(DeletionsAs::Ignore),
+            other => Err(format!("unknown way to count deletions `{}`", other)),
+        }
+    }
+}
+
+/// A color a repository is shown in, named in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+/// The colors given out to repositories without one of their own, leaving out
+/// the green and yellow of the committed and pending LoC.
+const AUTO_COLORS: [RepoColor; 8] = [
+    RepoColor::Cyan,
+    RepoColor::Magenta,
+    RepoColor::Blue,
+    RepoColor::BrightCyan,
+    RepoColor::BrightMagenta,
+    RepoColor::BrightBlue,
+    RepoColor::Red,
+    RepoColor::BrightRed,
+];
+
+impl RepoColor {
+    /// Picks a color for a repository from its name, so it keeps the same one across runs.
+    pub fn auto(repo_name: &str) -> Self {
+        let hash = repo_name.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte.into()));
+        AUTO_COLORS[hash as usize % AUTO_COLORS.len()]
+    }
+
+    /// The color in the terminal's palette.
+    pub fn ansi(self) -> AnsiColors {
+        match self {
+            RepoColor::Red => AnsiColors::Red,
+            RepoColor::Green => AnsiColors::Green,
+            RepoColor::Yellow => AnsiColors::Yellow,
+            RepoColor::Blue => AnsiColors::Blue,
+            RepoColor::Magenta => AnsiColors::Magenta,
+            RepoColor::Cyan => AnsiColors::Cyan,
+            RepoColor::White => AnsiColors::White,
+            RepoColor::BrightRed => AnsiColors::BrightRed,
+            RepoColor::BrightGreen => AnsiColors::BrightGreen,
+            RepoColor::BrightYellow => AnsiColors::BrightYellow,
+            RepoColor::BrightBlue => AnsiColors::BrightBlue,
+            RepoColor::BrightMagenta => AnsiColors::BrightMagenta,
+            RepoColor::BrightCyan => AnsiColors::BrightCyan,
+            RepoColor::BrightWhite => AnsiColors::BrightWhite,
+        }
+    }
+}
+
+impl FromStr for RepoColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "red" => Ok(RepoColor::Red),
+            "green" => Ok(RepoColor::Green),
+            "yellow" => Ok(RepoColor::Yellow),
+            "blue" => Ok(RepoColor::Blue),
+            "magenta" | "purple" => Ok(RepoColor::Magenta),
+            "cyan" => Ok(RepoColor::Cyan),
+            "white" => Ok(RepoColor::White),
+            "bright-red" => Ok(RepoColor::BrightRed),
+            "bright-green" => Ok(RepoColor::BrightGreen),
+            "bright-yellow" => Ok(RepoColor::BrightYellow),
+            "bright-blue" => Ok(RepoColor::BrightBlue),
+            "bright-magenta" | "bright-purple" => Ok(RepoColor::BrightMagenta),
+            "bright-cyan" => Ok(RepoColor::BrightCyan),
+            "bright-white" => Ok(RepoColor::BrightWhite),
+            _ => Err(format!("unknown color `{}`", s)),
+        }
+    }
+}
+
+/// How a repository is shown in the text output and the dashboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoStyle {
+    /// The label shown in place of the repository's name.
+    pub label: String,
+    /// The color of the label.
+    pub color: RepoColor,
+}
+
+/// Options controlling how the per-poll status is printed.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// The output format.
+    pub format: OutputFormat,
+    /// Whether to print a line per file extension under each repository.
+    pub by_language: bool,
+    /// Whether to print a line for code and one for docs under each repository.
+    pub by_category: bool,
+    /// Whether to print a line per author under each repository and the total.
+    pub by_author: bool,
+    /// Whether to append the net and churn LoC to each line of the text output.
+    pub show_net: bool,
+    /// Whether to append the number of files changed to each line of the text output.
+    pub show_files: bool,
+    /// Whether to append the split of the committed additions into new and edited code.
+    pub by_hunk: bool,
+    /// The committed LoC aimed for each day, shown as a progress bar.
+    pub daily_goal: Option<u32>,
+    /// Whether to color the committed LoC green and the pending LoC yellow.
+    pub color: bool,
+    /// How deleted lines count towards the LoC shown and the daily goal.
+    pub deletions_as: DeletionsAs,
+    /// The labels and colors set in the config file, by repository name.
+    pub repo_styles: BTreeMap<String, RepoStyle>,
+}
+
+impl DisplayOptions {
+    /// How a repository is shown: as configured, or by its name in a color of its own.
+    pub fn repo_style(&self, repo_name: &str) -> RepoStyle {
+        self.repo_styles.get(repo_name).cloned().unwrap_or_else(|| RepoStyle {
+            label: repo_name.to_string(),
+            color: RepoColor::auto(repo_name),
+        })
+    }
+}
+
+/// Whether the text output may be colored: stdout is a terminal, `NO_COLOR`
+/// is unset or empty, and colors were not turned off with `--no-color`.
+///
+/// # Arguments
+///
+/// * `no_color` - Whether `--no-color` was given.
+pub fn use_color(no_color: bool) -> bool {
+    !no_color
+        && std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// A single poll's status as emitted in JSON mode.
+#[derive(Serialize)]
+struct StatusLine<'a> {
+    timestamp: DateTime<Utc>,
+    repos: Vec<RepoLine<'a>>,
+    totals: RepoStats,
+    /// The lines added over every repository, committed and pending together.
+    additions: i32,
+    /// The lines deleted over every repository, committed and pending together.
+    deletions: i32,
+    streak: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daily_goal: Option<u32>,
+}
+
+/// The stats for one repository within a StatusLine.
+#[derive(Serialize)]
+struct RepoLine<'a> {
+    repo: &'a str,
+    #[serde(flatten)]
+    stats: &'a RepoStats,
+}
+
+/// Sums the stats of each repository over its authors.
+///
+/// # Arguments
+///
+/// * `author_stats` - The stats per repository and author.
+pub fn by_repo(author_stats: &BTreeMap<(String, String), RepoStats>) -> BTreeMap<String, RepoStats> {
+    let mut repo_stats: BTreeMap<String, RepoStats> = BTreeMap::new();
+    for ((repo_name, _), stats) in author_stats {
+        *repo_stats.entry(repo_name.clone()).or_default() += stats;
+    }
+    repo_stats
+}
+
+/// Renders the current stats of every repository in the requested format.
+///
+/// # Arguments
+///
+/// * `author_stats` - The latest stats per repository and author.
+/// * `display` - The options controlling what is rendered and how.
+/// * `timestamp` - The time of the poll being reported.
+/// * `streak` - The number of consecutive days with committed code.
+/// * `pace` - The committed LoC per minute over the latest polls, once known.
+///
+/// # Returns
+///
+/// The rendered status, ending with a newline.
+pub fn render(
+    author_stats: &BTreeMap<(String, String), RepoStats>,
+    display: &DisplayOptions,
+    timestamp: DateTime<Utc>,
+    streak: u32,
+    pace: Option<f64>,
+) -> String {
+    match display.format {
+        OutputFormat::Text => render_text(author_stats, display, streak, pace),
+        OutputFormat::Json => render_json(&by_repo(author_stats), timestamp, streak, display.daily_goal),
+    }
+}
+
+/// Renders the status as a single line: the time of the poll and the total over
+/// every repository, e.g. `14:02:11 Total: 120 LoC committed, 43 LoC In Progress`.
+///
+/// # Arguments
+///
+/// * `author_stats` - The latest stats per repository and author.
+/// * `previous` - The total before the poll, to append how much it moved, if known.
+/// * `display` - The options controlling how deletions count and whether to color.
+/// * `timestamp` - The time of the poll being reported.
+/// * `pace` - The committed LoC per minute over the latest polls, once known.
+///
+/// # Returns
+///
+/// The rendered line, ending with a newline.
+pub fn render_compact(
+    author_stats: &BTreeMap<(String, String), RepoStats>,
+    previous: Option<&RepoStats>,
+    display: &DisplayOptions,
+    timestamp: DateTime<Utc>,
+    pace: Option<f64>,
+) -> String {
+    let total = total(author_stats);
+    let (committed, pending) = (display.deletions_as.committed(&total), display.deletions_as.pending(&total));
+    let committed_text = format!("{} LoC committed", committed);
+    let pending_text = format!("{} LoC In Progress", pending);
+    let (committed_text, pending_text) = match display.color {
+        true => (committed_text.green().to_string(), pending_text.yellow().to_string()),
+        false => (committed_text, pending_text),
+    };
+
+    let time = timestamp.with_timezone(&Local).format("%H:%M:%S");
+    let mut line = format!("{} Total: {}, {}", time, committed_text, pending_text);
+    if let Some(previous) = previous {
+        line.push_str(&format!(
+            " ({} committed, {} In Progress since the last poll)",
+            format_signed(committed - display.deletions_as.committed(previous)),
+            format_signed(pending - display.deletions_as.pending(previous)),
+        ));
+    }
+    if let Some(pace) = pace {
+        line.push_str(&format!(", {}", format_pace(pace)));
+    }
+    line.push('\n');
+    line
+}
+
+/// Formats the lines added and deleted, committed and pending together, e.g.
+/// `+120 / -45`, with the deletions in red when colored.
+pub fn format_lines(stats: &RepoStats, color: bool) -> String {
+    let (additions, deletions) = (format!("+{}", stats.additions()), format!("-{}", stats.deletions()));
+    match color {
+        true => format!("{} / {}", additions.green(), deletions.red()),
+        false => format!("{} / {}", additions, deletions),
+    }
+}
+
+/// Formats the committed LoC per minute, e.g. `~12 LoC/min`.
+pub fn format_pace(pace: f64) -> String {
+    format!("~{:.0} LoC/min", pace)
+}
+
+/// Sums the stats of every repository and author.
+pub fn total(author_stats: &BTreeMap<(String, String), RepoStats>) -> RepoStats {
+    let mut total = RepoStats::default();
+    for stats in author_stats.values() {
+        total += stats;
+    }
+    total
+}
+
+fn render_text(
+    author_stats: &BTreeMap<(String, String), RepoStats>,
+    display: &DisplayOptions,
+    streak: u32,
+    pace: Option<f64>,
+) -> String {
+    let repo_stats = by_repo(author_stats);
+    let mut rows: Vec<Row> = Vec::new();
+    let mut total = RepoStats::default();
+    let mut author_totals: BTreeMap<&str, RepoStats> = BTreeMap::new();
+
+    for (repo_name, stats) in &repo_stats {
+        let style = display.repo_style(repo_name);
+        rows.push((format!("{}:", style.label), stats, Some(style.color)));
+        if display.by_author {
+            for ((_, author), stats) in author_stats.iter().filter(|((repo, _), _)| repo == repo_name) {
+                rows.push((format!("  {}:", author), stats, None));
+                *author_totals.entry(author).or_default() += stats;
+            }
+        }
+        if display.by_language {
+            for (extension, stats) in &stats.extensions {
+                let label = if extension.is_empty() { "(none)" } else { extension.as_str() };
+                rows.push((format!("  {}:", label), stats, None));
+            }
+        }
+        if display.by_category {
+            for (category, stats) in &stats.categories {
+                rows.push((format!("  {}:", category), stats, None));
+            }
+        }
+        total += stats;
+    }
+
+    // The totals are set apart by a blank line but aligned with the repositories
+    let repo_rows = rows.len();
+    rows.push(("Total:".to_string(), &total, None));
+    for (author, stats) in &author_totals {
+        rows.push((format!("  {}:", author), stats, None));
+    }
+
+    let mut output = String::new();
+    for (index, line) in aligned_lines(&rows, display).iter().enumerate() {
+        if index == repo_rows {
+            output.push('\n');
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    if let Some(goal) = display.daily_goal {
+        let committed = display.deletions_as.committed(&total);
+        output.push_str(&format!("Goal: {}\n", goal_progress(committed, goal)));
+    }
+    output.push_str(&format!("Lines: {}\n", format_lines(&total, display.color)));
+    if streak > 0 {
+        output.push_str(&format!("Streak: code committed {} day(s) in a row\n", streak));
+    }
+    if let Some(pace) = pace {
+        output.push_str(&format!("Pace: {}\n", format_pace(pace)));
+    }
+    output.push('\n');
+    output
+}
+
+/// A labelled line of the text output, with the color of the label if it has one.
+type Row<'a> = (String, &'a RepoStats, Option<RepoColor>);
+
+/// Formats a line per labelled row, padding the labels and the committed and
+/// pending LoC to the widest of each so the rows line up.
+fn aligned_lines(rows: &[Row], display: &DisplayOptions) -> Vec<String> {
+    let committed = |stats: &RepoStats| display.deletions_as.committed(stats).to_string();
+    let pending = |stats: &RepoStats| display.deletions_as.pending(stats).to_string();
+    let label_width = rows.iter().map(|(label, _, _)| label.chars().count()).max().unwrap_or(0);
+    let committed_width = rows.iter().map(|(_, stats, _)| committed(stats).len()).max().unwrap_or(0);
+    let pending_width = rows.iter().map(|(_, stats, _)| pending(stats).len()).max().unwrap_or(0);
+
+    rows.iter()
+        .map(|(label, stats, color)| {
+            let committed = format!("{:>width$} LoC committed", committed(stats), width = committed_width);
+            let pending = format!("{:>width$} LoC In Progress", pending(stats), width = pending_width);
+            let (committed, pending) = match display.color {
+                true => (committed.green().to_string(), pending.yellow().to_string()),
+                false => (committed, pending),
+            };
+            let summary = format!("{}, {}{}", committed, pending, loc_details(stats, display));
+            // The label is padded after coloring, as the escape codes take no room on screen
+            let padding = " ".repeat(label_width - label.chars().count());
+            let label = match (display.color, color) {
+                (true, Some(color)) => label.color(color.ansi()).to_string(),
+                _ => label.clone(),
+            };
+            format!("{}{} {}", label, padding, summary)
+        })
+        .collect()
+}
+
+/// Describes what follows the committed and pending LoC on a line of the text output.
+fn loc_details(stats: &RepoStats, display: &DisplayOptions) -> String {
+    let mut summary = String::new();
+    if stats.staged_additions + stats.staged_deletions > 0 {
+        let staged = display.deletions_as.loc(stats.staged_additions, stats.staged_deletions);
+        let unstaged = display.deletions_as.pending(stats) - staged;
+        summary.push_str(&format!(" ({} staged, {} unstaged)", staged, unstaged));
+    }
+    if display.show_net {
+        summary.push_str(&format!(", net {}, churn {}", format_signed(stats.net()), stats.churn()));
+    }
+    if display.show_files {
+        summary.push_str(&format!(
+            ", files {} committed, {} In Progress",
+            stats.committed_files, stats.pending_files
+        ));
+    }
+    if display.by_hunk {
+        summary.push_str(&format!(
+            ", committed {} new, {} edited",
+            stats.committed_inserted, stats.committed_edited
+        ));
+    }
+    summary
+}
+
+/// Draws the day's committed LoC against the daily goal, e.g.
+/// `[#####...............] 200/800 LoC (25%)`. A met goal fills the bar with `=`.
+///
+/// # Arguments
+///
+/// * `committed` - The LoC committed today.
+/// * `goal` - The committed LoC aimed for.
+pub fn goal_progress(committed: i32, goal: u32) -> String {
+    let (committed, goal) = (i64::from(committed.max(0)), i64::from(goal));
+    if committed >= goal {
+        let percent = if goal > 0 { committed * 100 / goal } else { 100 };
+        let bar = "=".repeat(GOAL_BAR_WIDTH as usize);
+        return format!("[{}] {}/{} LoC ({}%), goal met!", bar, committed, goal, percent);
+    }
+
+    let filled = committed * GOAL_BAR_WIDTH / goal;
+    let bar = format!("{}{}", "#".repeat(filled as usize), ".".repeat((GOAL_BAR_WIDTH - filled) as usize));
+    format!("[{}] {}/{} LoC ({}%)", bar, committed, goal, committed * 100 / goal)
+}
+
+/// Formats a number with an explicit sign, leaving zero unsigned.
+///
+/// # Arguments
+///
+/// * `value` - The number to format.
+pub fn format_signed(value: i32) -> String {
+    if value > 0 {
+        format!("+{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_json(
+    repo_stats: &BTreeMap<String, RepoStats>,
+    timestamp: DateTime<Utc>,
+    streak: u32,
+    daily_goal: Option<u32>,
+) -> String {
+    let mut totals = RepoStats::default();
+    let repos = repo_stats
+        .iter()
+        .map(|(repo, stats)| {
+            totals += stats;
+            RepoLine { repo, stats }
+        })
+        .collect();
+
+    let (additions, deletions) = (totals.additions(), totals.deletions());
+    let line = StatusLine { timestamp, repos, totals, additions, deletions, streak, daily_goal };
+    let mut output = serde_json::to_string(&line).expect("status serializes to JSON");
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_status_is_a_single_parseable_line() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 12, pending_deletions: 3, ..Default::default() },
+        );
+        repo_stats.insert(
+            ("web".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 1, pending_additions: 4, ..Default::default() },
+        );
+
+        let display = DisplayOptions { format: OutputFormat::Json, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 2, None);
+        assert_eq!(output.lines().count(), 1);
+
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(value["timestamp"].is_string());
+        assert_eq!(value["repos"][0]["repo"], "api");
+        assert_eq!(value["repos"][0]["committed_additions"], 12);
+        assert_eq!(value["repos"][1]["pending_additions"], 4);
+        assert_eq!(value["totals"]["committed_additions"], 13);
+        assert_eq!(value["totals"]["pending_deletions"], 3);
+        assert_eq!(value["streak"], 2);
+    }
+
+    #[test]
+    fn net_and_churn_are_shown_with_their_sign() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 2, committed_deletions: 10, pending_additions: 3, ..Default::default() },
+        );
+        repo_stats.insert(
+            ("web".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 9, ..Default::default() },
+        );
+
+        let display = DisplayOptions { show_net: true, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "api:   12 LoC committed, 3 LoC In Progress, net -5, churn 15");
+        assert_eq!(lines[1], "web:    9 LoC committed, 0 LoC In Progress, net +9, churn 9");
+        assert_eq!(lines[3], "Total: 21 LoC committed, 3 LoC In Progress, net +4, churn 24");
+        assert_eq!(format_signed(0), "0");
+    }
+
+    #[test]
+    fn repositories_line_up_and_counts_are_colored_on_request() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 120, pending_additions: 3, ..Default::default() },
+        );
+        repo_stats.insert(
+            ("frontend".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 7, pending_deletions: 40, ..Default::default() },
+        );
+
+        let output = render(&repo_stats, &DisplayOptions::default(), Utc::now(), 0, None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "api:      120 LoC committed,  3 LoC In Progress");
+        assert_eq!(lines[1], "frontend:   7 LoC committed, 40 LoC In Progress");
+        assert_eq!(lines[3], "Total:    127 LoC committed, 43 LoC In Progress");
+        assert!(!output.contains('\u{1b}'));
+
+        let display = DisplayOptions { color: true, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        let (green, yellow, reset) = ("\u{1b}[32m", "\u{1b}[33m", "\u{1b}[39m");
+        let label = "api:".color(RepoColor::auto("api").ansi()).to_string();
+        let expected =
+            format!("{}      {}120 LoC committed{}, {} 3 LoC In Progress{}", label, green, reset, yellow, reset);
+        assert_eq!(output.lines().next(), Some(expected.as_str()));
+    }
+
+    #[test]
+    fn compact_output_is_a_single_timestamped_total() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 100, pending_additions: 3, ..Default::default() },
+        );
+        repo_stats.insert(
+            ("web".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 20, pending_deletions: 40, ..Default::default() },
+        );
+        let timestamp = Utc::now();
+        let time = timestamp.with_timezone(&Local).format("%H:%M:%S").to_string();
+        let display = DisplayOptions::default();
+
+        let output = render_compact(&repo_stats, None, &display, timestamp, None);
+        assert_eq!(output, format!("{} Total: 120 LoC committed, 43 LoC In Progress\n", time));
+
+        let previous = RepoStats { committed_additions: 110, pending_additions: 50, ..Default::default() };
+        let output = render_compact(&repo_stats, Some(&previous), &display, timestamp, None);
+        let moved = "(+10 committed, -7 In Progress since the last poll)";
+        assert_eq!(output, format!("{} Total: 120 LoC committed, 43 LoC In Progress {}\n", time, moved));
+
+        assert!(render_compact(&repo_stats, None, &display, timestamp, Some(11.6)).ends_with(", ~12 LoC/min\n"));
+        assert!(render(&repo_stats, &display, timestamp, 0, Some(11.6)).contains("\nPace: ~12 LoC/min\n"));
+    }
+
+    #[test]
+    fn configured_repositories_get_their_label_and_color() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(("api".to_string(), "Me".to_string()), RepoStats { committed_additions: 5, ..Default::default() });
+        repo_stats.insert(("web".to_string(), "Me".to_string()), RepoStats { committed_additions: 2, ..Default::default() });
+        let mut repo_styles = BTreeMap::new();
+        repo_styles.insert("api".to_string(), RepoStyle { label: "Backend".to_string(), color: RepoColor::Red });
+
+        let display = DisplayOptions { repo_styles, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "Backend: 5 LoC committed, 0 LoC In Progress");
+        assert_eq!(lines[1], "web:     2 LoC committed, 0 LoC In Progress");
+
+        let display = DisplayOptions { color: true, ..display };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        assert!(output.starts_with("\u{1b}[31mBackend:\u{1b}[39m "));
+        assert!(AUTO_COLORS.contains(&RepoColor::auto("web")));
+
+        // The JSON output keeps the stored names
+        let display = DisplayOptions { format: OutputFormat::Json, ..display };
+        assert!(render(&repo_stats, &display, Utc::now(), 0, None).contains("\"repo\":\"api\""));
+
+        assert_eq!("Bright_Blue".parse(), Ok(RepoColor::BrightBlue));
+        assert!("mauve".parse::<RepoColor>().is_err());
+    }
+
+    #[test]
+    fn deletions_count_as_requested() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats {
+                committed_additions: 30,
+                committed_deletions: 50,
+                pending_additions: 6,
+                pending_deletions: 2,
+                staged_additions: 4,
+                staged_deletions: 1,
+                ..Default::default()
+            },
+        );
+
+        let first_lines = |deletions_as: DeletionsAs| {
+            let display = DisplayOptions { daily_goal: Some(100), deletions_as, ..Default::default() };
+            let output = render(&repo_stats, &display, Utc::now(), 0, None);
+            let lines: Vec<String> = output.lines().map(str::to_string).collect();
+            (lines[0].clone(), lines[3].clone())
+        };
+
+        let (line, goal) = first_lines(DeletionsAs::Positive);
+        assert_eq!(line, "api:   80 LoC committed, 8 LoC In Progress (5 staged, 3 unstaged)");
+        assert!(goal.ends_with(" 80/100 LoC (80%)"));
+
+        let (line, goal) = first_lines(DeletionsAs::Negative);
+        assert_eq!(line, "api:   -20 LoC committed, 4 LoC In Progress (3 staged, 1 unstaged)");
+        assert!(goal.ends_with(" 0/100 LoC (0%)"));
+
+        let (line, goal) = first_lines(DeletionsAs::Ignore);
+        assert_eq!(line, "api:   30 LoC committed, 6 LoC In Progress (4 staged, 2 unstaged)");
+        assert!(goal.ends_with(" 30/100 LoC (30%)"));
+
+        // The JSON output keeps the raw counts whatever the mode
+        let display = DisplayOptions { format: OutputFormat::Json, deletions_as: DeletionsAs::Ignore, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["totals"]["committed_deletions"], 50);
+    }
+
+    #[test]
+    fn files_changed_are_shown_on_request() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 4, committed_files: 3, pending_additions: 1, pending_files: 1, ..Default::default() },
+        );
+
+        let output = render(&repo_stats, &DisplayOptions::default(), Utc::now(), 0, None);
+        assert_eq!(output.lines().next(), Some("api:   4 LoC committed, 1 LoC In Progress"));
+
+        let display = DisplayOptions { show_files: true, ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "api:   4 LoC committed, 1 LoC In Progress, files 3 committed, 1 In Progress");
+        assert_eq!(lines[2], "Total: 4 LoC committed, 1 LoC In Progress, files 3 committed, 1 In Progress");
+    }
+
+    #[test]
+    fn daily_goal_shows_progress_until_it_is_met() {
+        assert_eq!(goal_progress(200, 800), "[#####...............] 200/800 LoC (25%)");
+        assert_eq!(goal_progress(0, 800), "[....................] 0/800 LoC (0%)");
+        assert_eq!(goal_progress(912, 800), "[====================] 912/800 LoC (114%), goal met!");
+
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            ("api".to_string(), "Me".to_string()),
+            RepoStats { committed_additions: 300, committed_deletions: 100, pending_additions: 50, ..Default::default() },
+        );
+        let display = DisplayOptions { daily_goal: Some(800), ..Default::default() };
+        let output = render(&repo_stats, &display, Utc::now(), 0, None);
+        assert_eq!(output.lines().nth(3), Some("Goal: [##########..........] 400/800 LoC (50%)"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Counting the consecutive days on which code was committed.
+
+use std::collections::BTreeSet;
+
+use chrono::{Duration, Local, NaiveDate};
+
+use crate::report::daily_totals_from;
+use crate::store::{self, Store};
+use crate::{day_of, ChangeFilter, StreakOpt};
+
+/// Runs the `streak` subcommand, printing the author's current streak.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed streak options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: StreakOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let days = active_days(store.as_ref(), std::iter::once(&opt.author), opt.day_start_hour).await?;
+    store.close().await;
+
+    let streak = current_streak(&days, day_of(&Local::now(), opt.day_start_hour));
+    println!("{} has committed code {} day(s) in a row", opt.author, streak);
+    Ok(())
+}
+
+/// Collects the local days on which any of the given authors committed additions,
+/// from the stored rows and from the daily summaries of days since pruned.
+///
+/// # Arguments
+///
+/// * `store` - The Store holding the changes.
+/// * `authors` - The authors whose days are collected.
+/// * `day_start_hour` - The hour days start at, so earlier commits count for the day before.
+///
+/// # Returns
+///
+/// A Result containing the set of active days.
+pub async fn active_days<'a>(
+    store: &dyn Store,
+    authors: impl Iterator<Item = &'a String>,
+    day_start_hour: u32,
+) -> Result<BTreeSet<NaiveDate>, sqlx::Error> {
+    let mut days = BTreeSet::new();
+    for author in authors {
+        let filter = ChangeFilter { author: Some(author.clone()), ..Default::default() };
+        let changes = store.query_range(&filter).await?;
+        days.extend(
+            daily_totals_from(&changes, day_start_hour)
+                .into_iter()
+                .filter(|change| change.is_committed && change.additions > 0)
+                .map(|change| day_of(&change.timestamp.with_timezone(&Local), day_start_hour)),
+        );
+        days.extend(store.summarized_days(author).await?);
+    }
+
+    Ok(days)
+}
+
+/// Counts the consecutive active days leading up to today.
+///
+/// A streak that ran through yesterday is still current while nothing has been
+/// committed today yet.
+///
+/// # Arguments
+///
+/// * `days` - The days with committed additions.
+/// * `today` - The day the streak has to reach.
+///
+/// # Returns
+///
+/// The length of the streak in days, 0 if it is broken.
+pub fn current_streak(days: &BTreeSet<NaiveDate>, today: NaiveDate) -> u32 {
+    let mut day = if days.contains(&today) { today } else { today - Duration::days(1) };
+    let mut streak = 0;
+    while days.contains(&day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::start_of_local_day;
+    use crate::store::SqliteStore;
+    use crate::{changes_from_stats, RepoStats};
+    use structopt::StructOpt;
+
+    #[test]
+    fn a_missing_day_breaks_the_streak() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let days_ago = |days: &[i64]| -> BTreeSet<NaiveDate> {
+            days.iter().map(|days| today - Duration::days(*days)).collect()
+        };
+
+        assert_eq!(current_streak(&days_ago(&[0, 1, 2, 4, 5]), today), 3);
+        assert_eq!(current_streak(&days_ago(&[1, 2, 4]), today), 2);
+        assert_eq!(current_streak(&days_ago(&[0, 2, 3]), today), 1);
+        assert_eq!(current_streak(&days_ago(&[2, 3]), today), 0);
+    }
+
+    #[tokio::test]
+    async fn active_days_follow_local_day_boundaries() {
+        let store = SqliteStore::memory().await;
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let first = start_of_local_day(day) + Duration::minutes(1);
+        let last = start_of_local_day(day + Duration::days(1)) - Duration::minutes(1);
+        let idle = start_of_local_day(day - Duration::days(1)) + Duration::hours(12);
+
+        let rows = [
+            // Both ends of the same local day
+            (RepoStats::with_commit("a1", first, 4, 0), first),
+            (RepoStats::with_commit("a2", last, 4, 0), last),
+            // Only pending work the day before does not count
+            (RepoStats { pending_additions: 9, ..Default::default() }, idle),
+        ];
+        for (stats, timestamp) in rows {
+            for change in &changes_from_stats("api", "Me", None, &stats, timestamp) {
+                store.store_change(change).await.unwrap();
+            }
+        }
+
+        let me = "Me".to_string();
+        let days = active_days(&store, std::iter::once(&me), 0).await.unwrap();
+        assert_eq!(days.into_iter().collect::<Vec<_>>(), [day]);
+    }
+
+    #[tokio::test]
+    async fn a_commit_before_the_day_start_hour_keeps_the_streak_going() {
+        let store = SqliteStore::memory().await;
+        let day = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        // Noon on the 11th, then 1am on the 13th, which is still the 12th when days start at 4am
+        let noon = start_of_local_day(day - Duration::days(1)) + Duration::hours(12);
+        let late = start_of_local_day(day + Duration::days(1)) + Duration::hours(1);
+        for (sha, at) in [("a1", noon), ("a2", late)] {
+            let stats = RepoStats::with_commit(sha, at, 4, 0);
+            store.store_changes(&changes_from_stats("api", "Me", None, &stats, at)).await.unwrap();
+        }
+
+        let opt = StreakOpt::from_iter(["streak", "--author", "Me", "--day-start-hour", "4"]);
+        let days = active_days(&store, std::iter::once(&opt.author), opt.day_start_hour).await.unwrap();
+        assert_eq!(current_streak(&days, day_of(&late.with_timezone(&Local), opt.day_start_hour)), 2);
+
+        // With days starting at midnight the 12th has no commit
+        let days = active_days(&store, std::iter::once(&opt.author), 0).await.unwrap();
+        assert_eq!(current_streak(&days, day_of(&late.with_timezone(&Local), 0)), 1);
+    }
+
+    #[tokio::test]
+    async fn days_pruned_into_daily_summaries_still_count() {
+        let store = SqliteStore::memory().await;
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        for days_ago in 0..5 {
+            let at = start_of_local_day(today - Duration::days(days_ago)) + Duration::hours(12);
+            let stats = RepoStats::with_commit(&format!("c{}", days_ago), at, 3, 0);
+            store.store_changes(&changes_from_stats("api", "Me", None, &stats, at)).await.unwrap();
+        }
+
+        // The two oldest days lose their rows and are left with their summaries
+        assert_eq!(crate::rollup::prune_old_changes(&store, 2, true, today, 0).await.unwrap(), 4);
+        let me = "Me".to_string();
+        let days = active_days(&store, std::iter::once(&me), 0).await.unwrap();
+        assert_eq!(current_streak(&days, today), 5);
+    }
+}
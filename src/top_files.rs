@@ -0,0 +1,154 @@
+//! The files with the most committed churn.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::report::align_table;
+use crate::{store, ChangeFilter, FileChange, RepoStats, TopFilesOpt};
+
+/// Runs the `top-files` subcommand, printing the most churned files.
+///
+/// # Arguments
+///
+/// * `opt` - The parsed top-files options.
+///
+/// # Returns
+///
+/// A Result indicating success or failure.
+pub async fn run(opt: TopFilesOpt) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = ChangeFilter {
+        author: opt.author,
+        ..ChangeFilter::for_range(opt.since, opt.until)?
+    };
+
+    let store = store::connect(&opt.db_path).await?;
+    store.setup().await?;
+    let files = store.query_file_changes(&filter).await?;
+    store.close().await;
+
+    print!("{}", format_top_files(&file_totals(&files), opt.limit));
+    Ok(())
+}
+
+/// Sums the committed changes of each file, a renamed file counting under its
+/// latest path along with its history from before the rename.
+///
+/// # Arguments
+///
+/// * `files` - The repository and counts of each file of every commit, ordered
+///   by commit time.
+///
+/// # Returns
+///
+/// A map from repository name and path to the committed totals of the file.
+pub fn file_totals(files: &[(String, FileChange)]) -> BTreeMap<(String, String), RepoStats> {
+    let mut totals: BTreeMap<(String, String), RepoStats> = BTreeMap::new();
+    // Walking back in time, the path a file had maps to where it was renamed to since
+    let mut renamed: HashMap<(&str, &str), String> = HashMap::new();
+
+    for (repo_name, file) in files.iter().rev() {
+        let path = renamed
+            .get(&(repo_name.as_str(), file.path.as_str()))
+            .cloned()
+            .unwrap_or_else(|| file.path.clone());
+        if let Some(old_path) = &file.old_path {
+            renamed.insert((repo_name, old_path), path.clone());
+        }
+
+        let stats = totals.entry((repo_name.clone(), path)).or_default();
+        stats.committed_additions += file.additions;
+        stats.committed_deletions += file.deletions;
+    }
+
+    totals
+}
+
+/// Formats the files with the most LoC changed as an aligned table, most churned first.
+///
+/// # Arguments
+///
+/// * `totals` - The committed totals per repository and path.
+/// * `limit` - How many files to include.
+pub fn format_top_files(totals: &BTreeMap<(String, String), RepoStats>, limit: usize) -> String {
+    let mut ranked: Vec<(&(String, String), &RepoStats)> = totals.iter().collect();
+    // Stable, so equally churned files stay ordered by repository and path
+    ranked.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.committed_additions + stats.committed_deletions));
+
+    let rows: Vec<Vec<String>> = ranked
+        .into_iter()
+        .take(limit)
+        .map(|((repo_name, path), stats)| {
+            vec![
+                repo_name.clone(),
+                path.clone(),
+                stats.committed_additions.to_string(),
+                stats.committed_deletions.to_string(),
+                (stats.committed_additions + stats.committed_deletions).to_string(),
+            ]
+        })
+        .collect();
+
+    align_table(&["Repository", "File"], &["Added", "Deleted", "Churn"], &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{SqliteStore, Store};
+    use crate::{changes_from_stats, CommitStats};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn files_are_ranked_by_churn_across_renames() {
+        let store = SqliteStore::memory().await;
+        let start = Utc::now() - Duration::hours(3);
+        let file = |path: &str, old_path: Option<&str>, additions: i32, deletions: i32| FileChange {
+            path: path.to_string(),
+            old_path: old_path.map(str::to_string),
+            additions,
+            deletions,
+        };
+        let commits = [
+            vec![file("src/main.rs", None, 10, 2), file("README.md", None, 4, 0)],
+            vec![file("README.md", None, 1, 1)],
+            // The rename carries the earlier churn of main.rs over to app.rs
+            vec![file("src/app.rs", Some("src/main.rs"), 3, 1)],
+        ];
+        for (hours, files) in commits.into_iter().enumerate() {
+            let timestamp = start + Duration::hours(hours as i64);
+            let commit_stats = RepoStats {
+                committed_additions: files.iter().map(|file| file.additions).sum(),
+                committed_deletions: files.iter().map(|file| file.deletions).sum(),
+                ..Default::default()
+            };
+            let commit = CommitStats {
+                sha: format!("c{}", hours),
+                author_email: None,
+                timestamp,
+                stats: commit_stats.clone(),
+                files,
+                flagged: false,
+                is_merge: false,
+            };
+            let stats = RepoStats { commits: vec![commit], ..commit_stats };
+            let rows = changes_from_stats("api", "Me", None, &stats, timestamp);
+            store.store_changes(&rows).await.unwrap();
+        }
+
+        let files = store.query_file_changes(&ChangeFilter::default()).await.unwrap();
+        assert_eq!(files.len(), 4);
+        let totals = file_totals(&files);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[&("api".to_string(), "src/app.rs".to_string())].committed_additions, 13);
+
+        let table = format_top_files(&totals, 10);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "Repository  File        Added  Deleted  Churn");
+        assert_eq!(lines[1], "api         src/app.rs     13        3     16");
+        assert_eq!(lines[2], "api         README.md       5        1      6");
+        assert_eq!(format_top_files(&totals, 1).lines().count(), 2);
+
+        // Another author's commits are left out
+        let filter = ChangeFilter { author: Some("Other".to_string()), ..Default::default() };
+        assert!(store.query_file_changes(&filter).await.unwrap().is_empty());
+    }
+}
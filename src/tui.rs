@@ -0,0 +1,221 @@
+//! A full-screen dashboard redrawn on every poll, shown with `--tui`.
+
+use std::collections::BTreeMap;
+use std::io::{self, Stdout};
+
+use chrono::{DateTime, Local};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::crossterm::ExecutableCommand;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Gauge, Row, Table};
+use ratatui::{Frame, Terminal};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::status::{format_pace, DeletionsAs, DisplayOptions, RepoColor};
+use crate::RepoStats;
+
+/// The terminal while the dashboard is shown; dropping it restores the terminal.
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    /// The daily goal, how deletions count and how each repository is labelled.
+    display: DisplayOptions,
+}
+
+impl Dashboard {
+    /// Switches the terminal to the dashboard and starts listening for the quit keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `display` - The options of the status; the daily goal, how deletions count
+    ///   and the labels and colors of the repositories are used.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the Dashboard and a receiver that gets a message once
+    /// `q` or Ctrl-C is pressed.
+    pub fn start(display: DisplayOptions) -> io::Result<(Self, UnboundedReceiver<()>)> {
+        terminal::enable_raw_mode()?;
+        io::stdout().execute(EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.clear()?;
+
+        // Raw mode turns Ctrl-C into a key press, so it is read here along with `q`
+        let (quit_tx, quit_rx) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                if let Event::Key(key) = event {
+                    let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c) {
+                        let _ = quit_tx.send(());
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok((Dashboard { terminal, display }, quit_rx))
+    }
+
+    /// Redraws the dashboard with the latest stats.
+    ///
+    /// # Arguments
+    ///
+    /// * `repo_stats` - The latest stats per repository.
+    /// * `updated` - The time of the poll the stats come from, if there was one yet.
+    /// * `streak` - The number of consecutive days with committed code.
+    /// * `pace` - The committed LoC per minute over the latest polls, once known.
+    pub fn draw(
+        &mut self,
+        repo_stats: &BTreeMap<String, RepoStats>,
+        updated: Option<DateTime<Local>>,
+        streak: u32,
+        pace: Option<f64>,
+    ) -> io::Result<()> {
+        let display = &self.display;
+        self.terminal.draw(|frame| render(frame, repo_stats, updated, streak, pace, display))?;
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = io::stdout().execute(LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// Lays out the table of repositories, its totals footer, the daily goal and the status line.
+fn render(
+    frame: &mut Frame,
+    repo_stats: &BTreeMap<String, RepoStats>,
+    updated: Option<DateTime<Local>>,
+    streak: u32,
+    pace: Option<f64>,
+    display: &DisplayOptions,
+) {
+    let (daily_goal, deletions_as) = (display.daily_goal, display.deletions_as);
+    let goal_height = if daily_goal.is_some() { 1 } else { 0 };
+    let [table_area, goal_area, status_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(goal_height), Constraint::Length(1)])
+            .areas(frame.area());
+
+    let mut total = RepoStats::default();
+    let mut rows: Vec<Row> = repo_stats
+        .iter()
+        .map(|(repo_name, stats)| {
+            total += stats;
+            let style = display.repo_style(repo_name);
+            stats_row(&style.label, Style::default().fg(terminal_color(style.color)), stats, deletions_as)
+        })
+        .collect();
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    rows.push(stats_row("Total", Style::default(), &total, deletions_as).style(bold));
+
+    let header = Row::new(["Repository", "Committed LoC", "Pending LoC"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let table = Table::new(
+        rows,
+        [Constraint::Fill(1), Constraint::Length(14), Constraint::Length(12)],
+    )
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(" DevMetrics "));
+    frame.render_widget(table, table_area);
+
+    if let Some(goal) = daily_goal {
+        let committed = deletions_as.committed(&total);
+        let ratio = if goal > 0 { f64::from(committed.max(0)) / f64::from(goal) } else { 1.0 };
+        let (color, label) = match ratio >= 1.0 {
+            true => (Color::Green, format!("{}/{} LoC, goal met!", committed, goal)),
+            false => (Color::Yellow, format!("{}/{} LoC", committed, goal)),
+        };
+        let gauge = Gauge::default().gauge_style(Style::default().fg(color)).ratio(ratio.min(1.0)).label(label);
+        frame.render_widget(gauge, goal_area);
+    }
+
+    let mut status = match updated {
+        Some(updated) => format!("Last update: {}", updated.format("%Y-%m-%d %H:%M:%S")),
+        None => "Waiting for the first poll".to_string(),
+    };
+    if streak > 0 {
+        status.push_str(&format!("  Streak: {} day(s)", streak));
+    }
+    if let Some(pace) = pace {
+        status.push_str(&format!("  Pace: {}", format_pace(pace)));
+    }
+    status.push_str("  (q to quit)");
+    frame.render_widget(Line::from(status), status_area);
+}
+
+/// The dashboard's color for a repository's color.
+fn terminal_color(color: RepoColor) -> Color {
+    match color {
+        RepoColor::Red => Color::Red,
+        RepoColor::Green => Color::Green,
+        RepoColor::Yellow => Color::Yellow,
+        RepoColor::Blue => Color::Blue,
+        RepoColor::Magenta => Color::Magenta,
+        RepoColor::Cyan => Color::Cyan,
+        RepoColor::White => Color::Gray,
+        RepoColor::BrightRed => Color::LightRed,
+        RepoColor::BrightGreen => Color::LightGreen,
+        RepoColor::BrightYellow => Color::LightYellow,
+        RepoColor::BrightBlue => Color::LightBlue,
+        RepoColor::BrightMagenta => Color::LightMagenta,
+        RepoColor::BrightCyan => Color::LightCyan,
+        RepoColor::BrightWhite => Color::White,
+    }
+}
+
+fn stats_row<'a>(label: &str, label_style: Style, stats: &RepoStats, deletions_as: DeletionsAs) -> Row<'a> {
+    Row::new([
+        Cell::from(label.to_string()).style(label_style),
+        Cell::from(Line::from(deletions_as.committed(stats).to_string()).right_aligned()),
+        Cell::from(Line::from(deletions_as.pending(stats).to_string()).right_aligned()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::status::RepoStyle;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn dashboard_lists_each_repository_and_the_totals() {
+        let mut repo_stats = BTreeMap::new();
+        repo_stats.insert(
+            "api".to_string(),
+            RepoStats { committed_additions: 10, committed_deletions: 2, pending_additions: 5, ..Default::default() },
+        );
+        repo_stats.insert(
+            "web".to_string(),
+            RepoStats { committed_additions: 1, pending_deletions: 4, ..Default::default() },
+        );
+        let updated = Local.with_ymd_and_hms(2024, 3, 12, 9, 30, 0).unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 8)).unwrap();
+        let mut display = DisplayOptions::default();
+        display.repo_styles.insert("web".to_string(), RepoStyle { label: "Frontend".to_string(), color: RepoColor::Red });
+        terminal.draw(|frame| render(frame, &repo_stats, Some(updated), 3, Some(12.4), &display)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let lines: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        let line_with = |label: &str| lines.iter().find(|line| line.contains(label)).unwrap().clone();
+
+        assert!(line_with("api").contains("12"));
+        assert!(line_with("Frontend").contains("4"));
+        let y = lines.iter().position(|line| line.contains("Frontend")).unwrap() as u16;
+        assert_eq!(buffer[(1, y)].fg, Color::Red);
+        let total = line_with("Total");
+        assert!(total.contains("13") && total.contains("9"));
+        assert!(lines[7].starts_with("Last update: 2024-03-12 09:30:00  Streak: 3 day(s)  Pace: ~12 LoC/min"));
+    }
+}
@@ -0,0 +1,97 @@
+//! The pace of committing, measured from one poll to the next.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use crate::status::DeletionsAs;
+use crate::RepoStats;
+
+/// How many of the latest poll intervals the pace is averaged over, so a single
+/// commit right after a poll does not send it soaring.
+pub const WINDOW: usize = 5;
+
+/// The committed totals of the latest polls, kept in memory only.
+#[derive(Debug, Clone, Default)]
+pub struct Velocity {
+    /// The time of each poll with the committed additions and deletions it found, oldest first.
+    samples: VecDeque<(DateTime<Utc>, i32, i32)>,
+}
+
+impl Velocity {
+    /// Records the committed totals found by a poll.
+    ///
+    /// Totals that went down, as they do when a new day starts, restart the
+    /// measurement from this poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The stats summed over every repository and author.
+    /// * `now` - The time of the poll.
+    pub fn record(&mut self, total: &RepoStats, now: DateTime<Utc>) {
+        let (additions, deletions) = (total.committed_additions, total.committed_deletions);
+        if let Some(&(_, last_additions, last_deletions)) = self.samples.back() {
+            if additions < last_additions || deletions < last_deletions {
+                self.samples.clear();
+            }
+        }
+        self.samples.push_back((now, additions, deletions));
+        if self.samples.len() > WINDOW + 1 {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The committed LoC per minute over the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `deletions_as` - How deleted lines count towards the LoC.
+    ///
+    /// # Returns
+    ///
+    /// The pace, or None until two polls some time apart were recorded.
+    pub fn loc_per_minute(&self, deletions_as: DeletionsAs) -> Option<f64> {
+        let (&(first_at, first_additions, first_deletions), &(last_at, last_additions, last_deletions)) =
+            (self.samples.front()?, self.samples.back()?);
+        let minutes = (last_at - first_at).num_milliseconds() as f64 / 60_000.0;
+        if minutes <= 0.0 {
+            return None;
+        }
+        let loc = deletions_as.loc(last_additions - first_additions, last_deletions - first_deletions);
+        Some(f64::from(loc) / minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn committed(additions: i32) -> RepoStats {
+        RepoStats { committed_additions: additions, ..Default::default() }
+    }
+
+    #[test]
+    fn the_pace_is_averaged_over_the_latest_polls() {
+        let start = Utc::now();
+        let mut velocity = Velocity::default();
+        velocity.record(&committed(100), start);
+        assert_eq!(velocity.loc_per_minute(DeletionsAs::Positive), None);
+
+        // A commit right after the baseline is spread over the time since
+        velocity.record(&committed(160), start + Duration::minutes(1));
+        assert_eq!(velocity.loc_per_minute(DeletionsAs::Positive), Some(60.0));
+        velocity.record(&committed(160), start + Duration::minutes(3));
+        assert_eq!(velocity.loc_per_minute(DeletionsAs::Positive), Some(20.0));
+
+        // Polls older than the window no longer count
+        for minute in 4..=8 {
+            velocity.record(&committed(160), start + Duration::minutes(minute));
+        }
+        assert_eq!(velocity.loc_per_minute(DeletionsAs::Positive), Some(0.0));
+
+        // A new day starts over from its first poll
+        velocity.record(&committed(0), start + Duration::minutes(9));
+        assert_eq!(velocity.loc_per_minute(DeletionsAs::Positive), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! End-to-end checks of `collect_stats` against temporary repositories with known history.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+use dev_metrics::{collect_stats, AuthorFilter, CollectOptions, RepoStats};
+use git2::{IndexAddOption, Repository, Signature, Time};
+use tempfile::TempDir;
+
+/// A repository in a temporary directory, removed when dropped.
+struct Fixture {
+    dir: TempDir,
+    repo: Repository,
+}
+
+impl Fixture {
+    /// Creates a repository whose first commit, by "Other", holds a one-line README.
+    ///
+    /// A root commit is never counted by the watcher, so every fixture starts from one.
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let fixture = Fixture { dir, repo };
+        fixture.write("README", 1);
+        fixture.commit("Other", Local::now(), "initial");
+        fixture
+    }
+
+    /// Writes a file of `lines` numbered lines, replacing what it held.
+    fn write(&self, path: &str, lines: usize) {
+        let path = self.dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        let contents: String = (1..=lines).map(|line| format!("line {}\n", line)).collect();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// Adds a file to the index without committing it.
+    fn stage(&self, path: &str) {
+        let mut index = self.repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+    }
+
+    /// Commits every file of the working directory as `author` at the given time.
+    fn commit(&self, author: &str, at: DateTime<Local>, message: &str) {
+        let mut index = self.repo.index().unwrap();
+        index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let time = Time::new(at.timestamp(), at.offset().local_minus_utc() / 60);
+        let sig = Signature::new(author, &format!("{}@example.com", author), &time).unwrap();
+        let parents: Vec<git2::Commit> =
+            self.repo.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+        self.repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parent_refs).unwrap();
+    }
+
+    /// Collects the stats of the given authors, the first owning the pending work.
+    fn stats(&self, authors: &[&str]) -> Vec<RepoStats> {
+        let names = authors.iter().map(|author| author.to_string()).collect();
+        let filter = AuthorFilter::new(names, Vec::new(), Default::default());
+        let mut author_stats = collect_stats(&self.repo, &filter, &CollectOptions::default()).unwrap();
+        authors.iter().map(|author| author_stats.remove(*author).unwrap()).collect()
+    }
+}
+
+/// An hour before the start of today, local time.
+fn yesterday() -> DateTime<Local> {
+    let midnight = Local::now().date_naive().and_hms_opt(0, 0, 0).unwrap();
+    Local.from_local_datetime(&midnight).earliest().unwrap() - Duration::hours(1)
+}
+
+#[test]
+fn a_clean_repository_has_nothing_to_count() {
+    let fixture = Fixture::new();
+
+    let [stats] = fixture.stats(&["Me"]).try_into().unwrap();
+    assert_eq!(stats, RepoStats::default());
+}
+
+#[test]
+fn staged_changes_are_pending_and_reported_as_staged() {
+    let fixture = Fixture::new();
+    fixture.write("src/lib.rs", 2);
+    fixture.commit("Me", Local::now(), "add lib");
+    fixture.write("src/lib.rs", 6);
+    fixture.stage("src/lib.rs");
+    fixture.write("src/lib.rs", 7);
+
+    let [stats] = fixture.stats(&["Me"]).try_into().unwrap();
+    assert_eq!((stats.committed_additions, stats.committed_files), (2, 1));
+    assert_eq!((stats.pending_additions, stats.staged_additions), (5, 4));
+    assert_eq!(stats.pending_files, 1);
+}
+
+#[test]
+fn untracked_files_count_as_pending_additions() {
+    let fixture = Fixture::new();
+    fixture.write("notes.txt", 3);
+    fixture.write("docs/todo.md", 2);
+
+    let [stats] = fixture.stats(&["Me"]).try_into().unwrap();
+    assert_eq!((stats.pending_additions, stats.pending_deletions, stats.staged_additions), (5, 0, 0));
+    assert_eq!(stats.pending_files, 2);
+    assert_eq!(stats.committed_additions, 0);
+}
+
+#[test]
+fn each_author_gets_only_their_own_commits() {
+    let fixture = Fixture::new();
+    fixture.write("a.txt", 3);
+    fixture.commit("Me", Local::now(), "mine");
+    fixture.write("b.txt", 4);
+    fixture.commit("Teammate", Local::now(), "theirs");
+    fixture.write("c.txt", 10);
+    fixture.commit("Stranger", Local::now(), "not tracked");
+    fixture.write("d.txt", 1);
+
+    let [mine, theirs] = fixture.stats(&["Me", "Teammate"]).try_into().unwrap();
+    assert_eq!((mine.committed_additions, mine.commits.len()), (3, 1));
+    assert_eq!((theirs.committed_additions, theirs.commits.len()), (4, 1));
+    // Uncommitted work belongs to the first author
+    assert_eq!((mine.pending_additions, theirs.pending_additions), (1, 0));
+}
+
+#[test]
+fn commits_from_yesterday_are_left_out() {
+    let fixture = Fixture::new();
+    fixture.write("old.txt", 8);
+    fixture.commit("Me", yesterday(), "yesterday's work");
+    fixture.write("new.txt", 2);
+    fixture.commit("Me", Local::now(), "today's work");
+
+    let [stats] = fixture.stats(&["Me"]).try_into().unwrap();
+    assert_eq!(stats.committed_additions, 2);
+    assert_eq!(stats.commits.len(), 1);
+    assert_eq!(stats.commits[0].timestamp.with_timezone(&Local).date_naive(), Local::now().date_naive());
+}
@@ -0,0 +1,54 @@
+//! Using the library directly, the way an editor plugin or another frontend would.
+
+use std::fs;
+
+use chrono::Utc;
+use dev_metrics::{changes_from_stats, collect_stats, open_repository, AuthorFilter, ChangeFilter, CollectOptions};
+use git2::{IndexAddOption, Repository, Signature};
+use tempfile::TempDir;
+
+/// Commits every file of the working directory as `author`, now.
+fn commit_all(repo: &Repository, author: &str, message: &str) {
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now(author, &format!("{}@example.com", author)).unwrap();
+    let parents: Vec<git2::Commit> = repo.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+    let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parent_refs).unwrap();
+}
+
+#[tokio::test]
+async fn stats_are_collected_and_stored_without_the_cli() {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(dir.path().join("README"), "hello\n").unwrap();
+    commit_all(&repo, "Other", "initial");
+    fs::write(dir.path().join("src/lib.rs"), "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+    commit_all(&repo, "Me", "add functions");
+    fs::write(dir.path().join("notes.txt"), "todo\n").unwrap();
+
+    let (repo, repo_name) = open_repository(&dir.path().join("src")).unwrap();
+    assert_eq!(Some(repo_name.as_str()), dir.path().file_name().and_then(|name| name.to_str()));
+
+    let authors = AuthorFilter::new(vec!["Me".to_string()], Vec::new(), Default::default());
+    let author_stats = collect_stats(&repo, &authors, &CollectOptions::default()).unwrap();
+    let stats = &author_stats["Me"];
+    assert_eq!((stats.committed_additions, stats.pending_additions), (3, 1));
+    assert_eq!(stats.commits.len(), 1);
+
+    let db_path = dir.path().join("stats.db");
+    let store = dev_metrics::store::connect(db_path.to_str().unwrap()).await.unwrap();
+    store.setup().await.unwrap();
+    for change in changes_from_stats(&repo_name, "Me", None, stats, Utc::now()) {
+        store.store_change(&change).await.unwrap();
+    }
+
+    let stored = store.query_range(&ChangeFilter::default()).await.unwrap();
+    store.close().await;
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().any(|change| change.is_committed && change.commit_sha == Some(stats.commits[0].sha.clone())));
+    assert!(stored.iter().any(|change| !change.is_committed && change.additions == 1));
+}